@@ -0,0 +1,14 @@
+fn main() {
+    // Generate the C header for the `ffi` feature into OUT_DIR. The copy in
+    // include/ is checked against it by the ffi tests.
+    #[cfg(feature = "ffi")]
+    {
+        let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+        let out_dir = std::env::var("OUT_DIR").unwrap();
+        cbindgen::generate(&crate_dir)
+            .expect("generating the C header")
+            .write_to_file(std::path::Path::new(&out_dir).join("ditto.h"));
+        println!("cargo:rerun-if-changed=src/ffi.rs");
+        println!("cargo:rerun-if-changed=cbindgen.toml");
+    }
+}
@@ -0,0 +1,116 @@
+/* Generated by cbindgen from src/ffi.rs; do not edit. */
+
+#ifndef DITTO_H
+#define DITTO_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// The outcome of a call.
+typedef enum DittoStatus {
+  // The call succeeded.
+  DITTO_STATUS_OK = 0,
+  // LibreOffice failed to convert the document.
+  DITTO_STATUS_CONVERSION_FAILED = 1,
+  // An argument was null, not a valid path or format, or an option was
+  // rejected.
+  DITTO_STATUS_INVALID_ARGUMENT = 2,
+  // The input is missing, is not a supported document, or is password
+  // protected.
+  DITTO_STATUS_INPUT_ERROR = 3,
+  // The output path is invalid, or the output exists and overwriting is
+  // disabled.
+  DITTO_STATUS_OUTPUT_ERROR = 4,
+  // LibreOffice (`soffice`) could not be started.
+  DITTO_STATUS_CONVERTER_NOT_FOUND = 5,
+  // The conversion did not finish within the timeout.
+  DITTO_STATUS_TIMEOUT = 6,
+} DittoStatus;
+
+// Conversion settings, created by `ditto_converter_new` and released
+// with `ditto_converter_free`.
+typedef struct DittoConverter DittoConverter;
+
+
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Converts `input_path` into `format` (an extension such as `"pdf"` or
+// `"odt"`, or null for PDF), writing `output_path`, with the default
+// settings.
+//
+// # Safety
+// `input_path` and `output_path` must point to NUL-terminated strings;
+// `format` must be null or do so.
+enum DittoStatus ditto_convert(const char *input_path, const char *output_path, const char *format);
+
+// Converts a `.docx` file to `.pdf` with the default settings.
+//
+// # Safety
+// `input_path` and `output_path` must point to NUL-terminated strings.
+enum DittoStatus ditto_docx_to_pdf(const char *input_path, const char *output_path);
+
+// Creates converter settings with the defaults of `ditto_convert`.
+struct DittoConverter *ditto_converter_new(void);
+
+// Releases converter settings. Null is ignored.
+//
+// # Safety
+// `converter` must be null or come from `ditto_converter_new`, and must
+// not be used afterwards.
+void ditto_converter_free(struct DittoConverter *converter);
+
+// Kills LibreOffice if a conversion takes longer than `timeout_ms`
+// milliseconds; 0 removes the limit.
+//
+// # Safety
+// `converter` must come from `ditto_converter_new`.
+enum DittoStatus ditto_converter_set_timeout_ms(struct DittoConverter *converter,
+                                                uint64_t timeout_ms);
+
+// Runs the LibreOffice executable at `program` rather than looking for
+// one.
+//
+// # Safety
+// `converter` must come from `ditto_converter_new`; `program` must point
+// to a NUL-terminated string.
+enum DittoStatus ditto_converter_set_program(struct DittoConverter *converter, const char *program);
+
+// Whether an existing output file is replaced (the default) or the
+// conversion fails with `DITTO_STATUS_OUTPUT_ERROR`.
+//
+// # Safety
+// `converter` must come from `ditto_converter_new`.
+enum DittoStatus ditto_converter_set_overwrite(struct DittoConverter *converter, bool overwrite);
+
+// Like `ditto_convert`, with the given settings.
+//
+// # Safety
+// `converter` must come from `ditto_converter_new`; the strings as for
+// `ditto_convert`.
+enum DittoStatus ditto_converter_convert(const struct DittoConverter *converter,
+                                         const char *input_path,
+                                         const char *output_path,
+                                         const char *format);
+
+// Returns the message of the last call on this thread that failed, or null
+// if it succeeded. Release the message with `ditto_string_free`.
+char *ditto_last_error_message(void);
+
+// Releases a string returned by ditto. Null is ignored.
+//
+// # Safety
+// `string` must be null or come from a ditto function, and must not be
+// used afterwards.
+void ditto_string_free(char *string);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* DITTO_H */
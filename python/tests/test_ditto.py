@@ -0,0 +1,183 @@
+"""Tests for the Python bindings, run against a stub soffice.
+
+Build the module into the current environment first, e.g. with
+``maturin develop``, then run ``pytest python/tests``.
+"""
+
+import os
+import stat
+import threading
+import zipfile
+
+import pytest
+
+import ditto
+
+# Writes <outdir>/<stem>.<ext> for every input, like soffice --convert-to.
+CONVERTING_SCRIPT = r"""#!/bin/sh
+while [ $# -gt 0 ]; do
+    case "$1" in
+        --convert-to) ext="${2%%:*}"; shift ;;
+        --outdir) outdir="$2"; shift ;;
+        -*) ;;
+        *)
+            name=$(basename "$1")
+            printf 'converted from %s\n' "$name" > "$outdir/${name%.*}.$ext"
+            ;;
+    esac
+    shift
+done
+"""
+
+
+def stub(tmp_path, body):
+    path = tmp_path / "soffice"
+    path.write_text(body)
+    path.chmod(path.stat().st_mode | stat.S_IXUSR)
+    return path
+
+
+@pytest.fixture
+def docx(tmp_path):
+    path = tmp_path / "report.docx"
+    with zipfile.ZipFile(path, "w") as archive:
+        archive.writestr("[Content_Types].xml", "<Types/>")
+        archive.writestr("word/document.xml", "")
+    return path
+
+
+def test_docx_to_pdf(tmp_path, docx):
+    soffice = stub(tmp_path, CONVERTING_SCRIPT)
+    output = tmp_path / "out" / "report.pdf"
+
+    result = ditto.docx_to_pdf(docx, output, soffice=soffice)
+
+    assert os.fspath(result) == os.fspath(output)
+    assert output.read_text() == "converted from report.docx\n"
+
+
+def test_convert_to_another_format(tmp_path, docx):
+    soffice = stub(tmp_path, CONVERTING_SCRIPT)
+    output = tmp_path / "report.odt"
+
+    ditto.convert(str(docx), str(output), "odt", soffice=str(soffice))
+
+    assert output.is_file()
+
+
+def test_errors_map_to_exception_classes(tmp_path, docx):
+    soffice = stub(tmp_path, CONVERTING_SCRIPT)
+
+    with pytest.raises(ditto.InputError):
+        ditto.docx_to_pdf(tmp_path / "missing.docx", tmp_path / "a.pdf", soffice=soffice)
+
+    existing = tmp_path / "existing.pdf"
+    existing.write_text("keep")
+    with pytest.raises(ditto.OutputError):
+        ditto.docx_to_pdf(docx, existing, soffice=soffice, overwrite=False)
+    assert existing.read_text() == "keep"
+
+    with pytest.raises(ditto.InvalidOptionsError):
+        ditto.docx_to_pdf(docx, tmp_path / "a.pdf", soffice=soffice, pdfa="4")
+
+    with pytest.raises(ditto.ConverterNotFoundError):
+        ditto.docx_to_pdf(docx, tmp_path / "a.pdf", soffice=tmp_path / "no-such-soffice")
+
+    with pytest.raises(ditto.ConversionFailedError):
+        ditto.docx_to_pdf(docx, tmp_path / "a.pdf", soffice=stub(tmp_path, "#!/bin/sh\nexit 1\n"))
+
+    with pytest.raises(ditto.ConversionTimeoutError) as raised:
+        ditto.docx_to_pdf(
+            docx, tmp_path / "a.pdf", soffice=stub(tmp_path, "#!/bin/sh\nsleep 30\n"), timeout=0.2
+        )
+    assert isinstance(raised.value, ditto.DittoError)
+
+
+def test_gil_is_released_while_soffice_runs(tmp_path, docx):
+    soffice = stub(tmp_path, "#!/bin/sh\nsleep 1\n" + CONVERTING_SCRIPT.split("\n", 1)[1])
+    ticks = []
+
+    def tick():
+        for _ in range(5):
+            ticks.append(None)
+            threading.Event().wait(0.05)
+
+    ticker = threading.Thread(target=tick)
+    conversion = threading.Thread(
+        target=ditto.docx_to_pdf, args=(docx, tmp_path / "report.pdf"), kwargs={"soffice": soffice}
+    )
+    conversion.start()
+    ticker.start()
+    ticker.join(timeout=0.8)
+    assert len(ticks) == 5
+    conversion.join()
+
+
+def test_pdf_options_reach_the_export_filter(tmp_path, docx):
+    soffice = stub(
+        tmp_path,
+        '#!/bin/sh\nprintf "%s\\n" "$@" > "$(dirname "$0")/args.txt"\n'
+        + CONVERTING_SCRIPT.split("\n", 1)[1],
+    )
+    options = ditto.PdfOptions(jpeg_quality=80, reduce_image_resolution=300, export_notes=False)
+
+    ditto.docx_to_pdf(docx, tmp_path / "report.pdf", soffice=soffice, pdf_options=options)
+
+    args = (tmp_path / "args.txt").read_text()
+    assert '"Quality":{"type":"long","value":"80"}' in args
+    assert '"MaxImageResolution":{"type":"long","value":"300"}' in args
+    assert '"ExportNotes":{"type":"boolean","value":"false"}' in args
+
+
+def test_encrypted_pdf_passwords_stay_off_command_lines(tmp_path, docx, monkeypatch):
+    record = '#!/bin/sh\nprintf "%s\\n" "$@" >> "$(dirname "$0")/args.txt"\n'
+    soffice = stub(tmp_path, record)
+    # Stands in for the UNO helper: keeps its filter data, writes the output.
+    python = tmp_path / "python"
+    python.write_text(
+        record
+        + 'printf "%s" "$DITTO_FILTER_DATA" > "$(dirname "$0")/filter-data.txt"\n'
+        + 'while [ $# -gt 0 ]; do\n'
+        + '  case "$1" in --output) output="${2#file://}"; shift ;; esac\n'
+        + "  shift\n"
+        + "done\n"
+        + 'echo converted > "$output"\n'
+    )
+    python.chmod(python.stat().st_mode | stat.S_IXUSR)
+    monkeypatch.setenv("DITTO_PYTHON", str(python))
+    options = ditto.PdfOptions(
+        open_password="open-sesame",
+        owner_password="owner-sesame",
+        printing="low",
+        copying=False,
+    )
+
+    ditto.docx_to_pdf(docx, tmp_path / "report.pdf", soffice=soffice, pdf_options=options)
+
+    assert "sesame" not in (tmp_path / "args.txt").read_text()
+    filter_data = (tmp_path / "filter-data.txt").read_text()
+    assert '"DocumentOpenPassword":{"type":"string","value":"open-sesame"}' in filter_data
+    assert '"Printing":{"type":"long","value":"1"}' in filter_data
+    assert '"EnableCopyingOfContent":{"type":"boolean","value":"false"}' in filter_data
+
+
+def test_invalid_pdf_options_are_rejected():
+    with pytest.raises(ditto.InvalidOptionsError):
+        ditto.PdfOptions(jpeg_quality=0)
+    with pytest.raises(ditto.InvalidOptionsError):
+        ditto.PdfOptions(jpeg_quality=300)
+    with pytest.raises(ditto.InvalidOptionsError):
+        ditto.PdfOptions(jpeg_quality=-1)
+    with pytest.raises(ditto.InvalidOptionsError):
+        ditto.PdfOptions(reduce_image_resolution=-300)
+    with pytest.raises(ditto.InvalidOptionsError):
+        ditto.PdfOptions(reduce_image_resolution=2**70)
+    with pytest.raises(ditto.InvalidOptionsError):
+        ditto.PdfOptions(printing="sometimes", owner_password="owner")
+    with pytest.raises(ditto.InvalidOptionsError):
+        ditto.PdfOptions(copying=False)
+
+
+def test_formats_lists_output_formats():
+    assert "pdf" in ditto.FORMATS
+    assert "xlsx" in ditto.FORMATS
@@ -0,0 +1,200 @@
+//! Conversions that wait on the tokio runtime instead of blocking a thread.
+//!
+//! Available with the `async` cargo feature. LibreOffice runs as a
+//! [`tokio::process`] child: the calling task is suspended while it works,
+//! and dropping the future (for example when a request is cancelled) kills
+//! LibreOffice together with every process it spawned.
+
+use crate::process;
+use crate::profile::Profile;
+use crate::{ConversionReport, Converter, DittoError, OutputFormat, Result};
+use std::path::Path;
+
+impl Converter {
+    /// Async version of [`Converter::docx_to_pdf`].
+    pub async fn docx_to_pdf_async(
+        &self,
+        input_path: &Path,
+        output_path: &Path,
+    ) -> Result<ConversionReport> {
+        self.convert_async(input_path, output_path, OutputFormat::Pdf)
+            .await
+    }
+
+    /// Async version of [`Converter::convert`].
+    ///
+    /// The configured timeout is enforced on the runtime's timer. Dropping
+    /// the returned future kills LibreOffice.
+    ///
+    /// Inputs opened with [`Converter::input_password`], and encrypted PDFs,
+    /// are converted over UNO on tokio's blocking thread pool; dropping the
+    /// future does not stop those.
+    ///
+    /// With a [`Converter::retry`] policy, the wait between attempts is
+    /// spent on the runtime's timer as well (on the blocking thread pool for
+    /// conversions over UNO).
+    pub async fn convert_async(
+        &self,
+        input_path: &Path,
+        output_path: &Path,
+        format: OutputFormat,
+    ) -> Result<ConversionReport> {
+        if self.needs_uno(format) {
+            let converter = self.clone();
+            let input_path = input_path.to_path_buf();
+            let output_path = output_path.to_path_buf();
+            return tokio::task::spawn_blocking(move || {
+                converter.convert(&input_path, &output_path, format)
+            })
+            .await
+            .map_err(|error| DittoError::Io(error.into()))?;
+        }
+
+        // Every attempt runs with the same profile; see `convert_using`.
+        let profile = Profile::prepare(&self.profile)?;
+        let mut attempt = 1;
+        loop {
+            let error = match self
+                .attempt_async(input_path, output_path, format, &profile)
+                .await
+            {
+                Ok(report) => {
+                    return Ok(ConversionReport {
+                        attempts: attempt,
+                        ..report
+                    })
+                }
+                Err(error) => error,
+            };
+            let Some(delay) = self.retry_delay(attempt, &error) else {
+                return Err(error);
+            };
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Runs one attempt of [`Converter::convert_async`].
+    async fn attempt_async(
+        &self,
+        input_path: &Path,
+        output_path: &Path,
+        format: OutputFormat,
+        profile: &Profile,
+    ) -> Result<ConversionReport> {
+        let prepared = self.prepare(input_path, output_path, format)?;
+        let (command, program) = self.cli_command(&prepared, profile)?;
+        let captured = process::run_async(command, self.timeout)
+            .await
+            .map_err(|source| DittoError::from_spawn(program, source))?;
+        self.finish(prepared, captured)
+    }
+}
+
+/// Async version of [`crate::convert`].
+///
+/// # Example
+/// ```no_run
+/// use std::path::Path;
+/// use ditto::OutputFormat;
+///
+/// # async fn run() -> ditto::Result<()> {
+/// ditto::asynchronous::convert(Path::new("in.docx"), Path::new("out.pdf"), OutputFormat::Pdf)
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn convert(
+    input_path: &Path,
+    output_path: &Path,
+    format: OutputFormat,
+) -> Result<ConversionReport> {
+    Converter::new()
+        .convert_async(input_path, output_path, format)
+        .await
+}
+
+/// Async version of [`crate::docx_to_pdf`].
+pub async fn docx_to_pdf(input_path: &Path, output_path: &Path) -> Result<ConversionReport> {
+    Converter::new()
+        .docx_to_pdf_async(input_path, output_path)
+        .await
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::test_support::{fake_soffice, write_input, CONVERTING_SCRIPT};
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_convert_async_with_fake_soffice() {
+        let temp_dir = tempdir().unwrap();
+        let soffice = fake_soffice(temp_dir.path(), CONVERTING_SCRIPT);
+        let input = write_input(temp_dir.path(), "report.docx");
+        let output = temp_dir.path().join("report.pdf");
+
+        let report = Converter::new()
+            .program(&soffice)
+            .docx_to_pdf_async(&input, &output)
+            .await
+            .unwrap();
+
+        assert_eq!(report.output_path, output);
+        assert!(report.stdout.starts_with("convert "));
+        assert!(output.is_file());
+    }
+
+    #[tokio::test]
+    async fn test_convert_async_times_out() {
+        let temp_dir = tempdir().unwrap();
+        let soffice = fake_soffice(temp_dir.path(), "sleep 30\n");
+        let input = write_input(temp_dir.path(), "report.docx");
+
+        let result = Converter::new()
+            .program(&soffice)
+            .timeout(Duration::from_millis(200))
+            .docx_to_pdf_async(&input, &temp_dir.path().join("report.pdf"))
+            .await;
+
+        assert!(matches!(result, Err(DittoError::Timeout { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_dropping_the_future_kills_soffice() {
+        let temp_dir = tempdir().unwrap();
+        // Like soffice.bin, the real work happens in a child process.
+        let soffice = fake_soffice(
+            temp_dir.path(),
+            "sleep 30 &\necho $! > \"$(dirname \"$0\")/worker.pid\"\nwait\n",
+        );
+        let input = write_input(temp_dir.path(), "report.docx");
+        let converter = Converter::new().program(&soffice);
+        let output = temp_dir.path().join("report.pdf");
+
+        let pid_file = temp_dir.path().join("worker.pid");
+        let conversion = converter.docx_to_pdf_async(&input, &output);
+        let wait_for_start = async {
+            while std::fs::read_to_string(&pid_file).map_or(true, |pid| pid.trim().is_empty()) {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        };
+        tokio::select! {
+            _ = conversion => panic!("conversion finished"),
+            _ = wait_for_start => {}
+        }
+
+        let pid = std::fs::read_to_string(&pid_file).unwrap();
+        let proc_dir = Path::new("/proc").join(pid.trim());
+        for _ in 0..100 {
+            // Gone, or a zombie waiting to be reaped.
+            match std::fs::read_to_string(proc_dir.join("stat")) {
+                Err(_) => return,
+                Ok(stat) if stat.contains(") Z ") => return,
+                Ok(_) => tokio::time::sleep(Duration::from_millis(20)).await,
+            }
+        }
+        panic!("soffice's child is still running");
+    }
+}
@@ -0,0 +1,1281 @@
+//! Configurable entry point for running conversions.
+
+use crate::format::OutputFormat;
+use crate::locate;
+use crate::pdf::PdfExportOptions;
+use crate::persist;
+use crate::pool::{PoolOptions, WorkerPool};
+use crate::process::{self, Captured};
+use crate::profile::{Profile, ProfileMode};
+use crate::queue::ConversionQueue;
+use crate::retry::RetryPolicy;
+use crate::secret::Secret;
+use crate::sniff::{self, DocumentFamily, InputFormat};
+use crate::uno;
+use crate::{BatchResult, ConversionReport, DittoError, Result};
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+/// Runs LibreOffice conversions with a given configuration.
+///
+/// The free function [`crate::docx_to_pdf`] uses a default `Converter`;
+/// build one yourself to change how LibreOffice is invoked.
+///
+/// Unless [`Converter::program`] is set, the `soffice` executable is taken
+/// from the [`SOFFICE_ENV`](crate::SOFFICE_ENV) environment variable, then
+/// from well-known install locations such as `/opt/libreoffice*/program`,
+/// and finally from `PATH`.
+///
+/// # Example
+/// ```no_run
+/// use std::path::{Path, PathBuf};
+/// use std::time::Duration;
+/// use ditto::Converter;
+///
+/// let converter = Converter::new().timeout(Duration::from_secs(120));
+/// converter.docx_to_pdf(Path::new("in.docx"), Path::new("out.pdf"))?;
+/// # Ok::<(), ditto::DittoError>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Converter {
+    program: Option<PathBuf>,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) profile: ProfileMode,
+    skip_input_check: bool,
+    pub(crate) max_input_size: Option<u64>,
+    no_overwrite: bool,
+    scratch_dir: Option<PathBuf>,
+    pub(crate) pdf_options: PdfExportOptions,
+    input_password: Option<Secret>,
+    python: Option<PathBuf>,
+    pub(crate) retry: Option<RetryPolicy>,
+}
+
+impl Converter {
+    /// Creates a converter with the default configuration: no timeout and an
+    /// isolated LibreOffice profile for every conversion.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs the given `soffice` executable instead of searching for one.
+    pub fn program(mut self, program: impl Into<PathBuf>) -> Self {
+        self.program = Some(program.into());
+        self
+    }
+
+    /// The `soffice` executable conversions will run.
+    pub fn program_path(&self) -> PathBuf {
+        locate::resolve(self.program.as_deref())
+    }
+
+    /// Kills LibreOffice (and every process it spawned) if a conversion
+    /// takes longer than `timeout`, failing with [`DittoError::Timeout`].
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Selects the LibreOffice user profile conversions run with.
+    ///
+    /// The default, [`ProfileMode::Isolated`], lets conversions run
+    /// concurrently from any number of threads.
+    pub fn profile(mut self, profile: ProfileMode) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Turns the input format check off (it is on by default).
+    ///
+    /// Before launching LibreOffice, the converter looks at the input's
+    /// contents. Inputs whose extension names a document format they do not
+    /// contain are rejected with [`DittoError::UnrecognizedInput`], and
+    /// documents that cannot be exported to the requested format with
+    /// [`DittoError::UnsupportedConversion`]. Inputs with other extensions
+    /// (CSV, HTML, ...) are passed through unchecked.
+    pub fn check_input(mut self, check: bool) -> Self {
+        self.skip_input_check = !check;
+        self
+    }
+
+    /// Whether an existing output file may be replaced (the default).
+    ///
+    /// With `false`, conversions whose output already exists fail with
+    /// [`DittoError::OutputExists`], checked before LibreOffice starts and
+    /// again, atomically where the filesystem supports hard links, when the
+    /// converted file is moved into place.
+    pub fn overwrite(mut self, overwrite: bool) -> Self {
+        self.no_overwrite = !overwrite;
+        self
+    }
+
+    /// Lets LibreOffice write converted files into `dir` (a tmpfs, say)
+    /// instead of next to the output.
+    ///
+    /// Finished files are then moved to the output path, by copying them
+    /// when `dir` is on another filesystem.
+    pub fn scratch_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.scratch_dir = Some(dir.into());
+        self
+    }
+
+    /// Rejects in-memory and streamed inputs larger than `bytes` with
+    /// [`DittoError::InputTooLarge`].
+    ///
+    /// Applies to [`Converter::convert_bytes`] and
+    /// [`Converter::convert_stream`]; a stream is cut off as soon as it
+    /// exceeds the limit, so an oversized upload is never fully buffered.
+    pub fn max_input_size(mut self, bytes: u64) -> Self {
+        self.max_input_size = Some(bytes);
+        self
+    }
+
+    /// Sets the options used when exporting to PDF.
+    ///
+    /// The options are ignored for other output formats.
+    pub fn pdf_options(mut self, options: PdfExportOptions) -> Self {
+        self.pdf_options = options;
+        self
+    }
+
+    /// Opens password-protected inputs with `password`.
+    ///
+    /// Encrypted inputs are rejected with [`DittoError::EncryptedInput`]
+    /// unless a password is set. `soffice --convert-to` cannot pass one, so
+    /// with a password LibreOffice is instead started as a UNO listener and
+    /// driven by a Python helper script; see [`Converter::python`]. The
+    /// password is handed to the helper through its environment, never on a
+    /// command line.
+    pub fn input_password(mut self, password: impl Into<String>) -> Self {
+        self.input_password = Some(Secret::new(password.into()));
+        self
+    }
+
+    /// Runs the UNO helper used for [`Converter::input_password`] and for
+    /// encrypted PDF output (see [`PdfExportOptions::open_password`]) with
+    /// the given Python interpreter, which must be able to `import uno`.
+    ///
+    /// By default the [`PYTHON_ENV`](crate::PYTHON_ENV) environment variable
+    /// is used, then the interpreter bundled with LibreOffice, then
+    /// `python3` from `PATH`.
+    pub fn python(mut self, python: impl Into<PathBuf>) -> Self {
+        self.python = Some(python.into());
+        self
+    }
+
+    /// Tries conversions that fail with a transient error again, as
+    /// `policy` says.
+    ///
+    /// Without a policy every conversion is attempted once. Batches
+    /// ([`Converter::convert_batch`]) are never retried.
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// Converts a `.docx` file to `.pdf`.
+    ///
+    /// Equivalent to [`Converter::convert`] with [`OutputFormat::Pdf`].
+    pub fn docx_to_pdf(&self, input_path: &Path, output_path: &Path) -> Result<ConversionReport> {
+        self.convert(input_path, output_path, OutputFormat::Pdf)
+    }
+
+    /// Converts any document LibreOffice can open into `format`.
+    ///
+    /// See [`crate::convert`] for the validation performed and the errors
+    /// returned. In addition, this fails with [`DittoError::Timeout`] when a
+    /// timeout is configured and LibreOffice does not finish in time.
+    pub fn convert(
+        &self,
+        input_path: &Path,
+        output_path: &Path,
+        format: OutputFormat,
+    ) -> Result<ConversionReport> {
+        self.convert_using(input_path, output_path, format, None, None)
+    }
+
+    /// Starts a pool of long-running LibreOffice workers that convert with
+    /// this configuration.
+    ///
+    /// Fails with [`DittoError::ConverterNotFound`] if LibreOffice cannot be
+    /// started.
+    pub fn start_pool(&self, options: PoolOptions) -> Result<WorkerPool> {
+        let program = self.program_path();
+        let python = locate::resolve_python(self.python.as_deref(), &program);
+        WorkerPool::start(self.clone(), program, python, options)
+    }
+
+    /// Starts a queue that runs conversions with this configuration, at most
+    /// `max_concurrent` at a time.
+    pub fn start_queue(&self, max_concurrent: usize) -> ConversionQueue {
+        ConversionQueue::start(self.clone(), max_concurrent)
+    }
+}
+
+impl Converter {
+    /// [`Converter::convert`], running LibreOffice through `pool` if given.
+    ///
+    /// Setting `cancelled` kills LibreOffice and fails the conversion with
+    /// [`DittoError::Cancelled`]. Conversions over UNO only check it before
+    /// they start.
+    pub(crate) fn convert_using(
+        &self,
+        input_path: &Path,
+        output_path: &Path,
+        format: OutputFormat,
+        pool: Option<&WorkerPool>,
+        cancelled: Option<&AtomicBool>,
+    ) -> Result<ConversionReport> {
+        let is_cancelled = || cancelled.is_some_and(|cancelled| cancelled.load(Ordering::SeqCst));
+        // Shared by every attempt: a fresh profile for each retry would run
+        // into the same first-start failures again.
+        let profile;
+        let runner = match pool {
+            Some(pool) => Runner::Pool(pool),
+            None => {
+                profile = Profile::prepare(&self.profile)?;
+                Runner::Profile(&profile)
+            }
+        };
+        let mut attempt = 1;
+        loop {
+            let error = match self.attempt(input_path, output_path, format, runner, cancelled) {
+                Ok(report) => {
+                    return Ok(ConversionReport {
+                        attempts: attempt,
+                        ..report
+                    })
+                }
+                Err(error) => error,
+            };
+            let Some(delay) = self.retry_delay(attempt, &error) else {
+                return Err(error);
+            };
+            // Sleep in short steps so that cancelling is not held up.
+            let deadline = Instant::now() + delay;
+            while !is_cancelled() && Instant::now() < deadline {
+                thread::sleep(process::POLL_INTERVAL.min(deadline - Instant::now()));
+            }
+            attempt += 1;
+        }
+    }
+
+    /// How long to wait before attempting a conversion again after attempt
+    /// number `attempt` failed with `error`, or `None` to give up.
+    pub(crate) fn retry_delay(&self, attempt: u32, error: &DittoError) -> Option<Duration> {
+        match error {
+            DittoError::Cancelled => None,
+            error => self.retry.as_ref()?.delay_after(attempt, error),
+        }
+    }
+
+    /// Runs one attempt of [`Converter::convert_using`].
+    fn attempt(
+        &self,
+        input_path: &Path,
+        output_path: &Path,
+        format: OutputFormat,
+        runner: Runner<'_>,
+        cancelled: Option<&AtomicBool>,
+    ) -> Result<ConversionReport> {
+        let is_cancelled = || cancelled.is_some_and(|cancelled| cancelled.load(Ordering::SeqCst));
+        let prepared = self.prepare(input_path, output_path, format)?;
+        if is_cancelled() {
+            return Err(DittoError::Cancelled);
+        }
+        let captured = match runner {
+            Runner::Profile(profile) if !self.needs_uno(format) => {
+                let (command, program) = self.cli_command(&prepared, profile)?;
+                process::run_cancellable(command, self.timeout, cancelled)
+                    .map_err(|source| DittoError::from_spawn(program, source))?
+            }
+            runner => self.run_uno(&prepared, runner)?,
+        };
+        if is_cancelled() {
+            return Err(DittoError::Cancelled);
+        }
+        self.finish(prepared, captured)
+    }
+
+    /// Validates the paths and the input, and creates the output directory.
+    pub(crate) fn prepare<'a>(
+        &self,
+        input_path: &'a Path,
+        output_path: &'a Path,
+        format: OutputFormat,
+    ) -> Result<Prepared<'a>> {
+        check_input_path(input_path)?;
+
+        if output_path.exists() && output_path.is_dir() {
+            return Err(DittoError::OutputIsDirectory(output_path.to_path_buf()));
+        }
+        check_not_input(input_path, output_path)?;
+
+        let output_dir = match output_path.parent() {
+            // A bare file name has an empty parent: the current directory.
+            Some(dir) if dir.as_os_str().is_empty() => Path::new("."),
+            Some(dir) => dir,
+            None => return Err(DittoError::OutputIsDirectory(output_path.to_path_buf())),
+        };
+        std::fs::create_dir_all(output_dir)?;
+
+        if self.no_overwrite && output_path.exists() {
+            return Err(DittoError::OutputExists(output_path.to_path_buf()));
+        }
+
+        let inspection = self.inspect(input_path, format)?;
+        // LibreOffice writes into a private directory, so that the finished
+        // file can be moved into place atomically.
+        let staging = self.staging_dir(output_dir)?;
+        let generated = staging.path().join(converted_name(input_path, format)?);
+        Ok(Prepared {
+            input_path,
+            output_path,
+            format,
+            inspection,
+            staging,
+            generated,
+        })
+    }
+
+    /// Whether conversions to `format` must go through UNO rather than
+    /// `--convert-to`.
+    pub(crate) fn needs_uno(&self, format: OutputFormat) -> bool {
+        self.input_password.is_some() || self.encrypts_pdf(format)
+    }
+
+    /// Whether conversions to `format` produce an encrypted PDF, whose
+    /// passwords must not be put on a command line.
+    fn encrypts_pdf(&self, format: OutputFormat) -> bool {
+        format == OutputFormat::Pdf && self.pdf_options.is_encrypted()
+    }
+
+    /// Converts over UNO, on a worker of `pool` or a one-off listener.
+    fn run_uno(&self, prepared: &Prepared<'_>, runner: Runner<'_>) -> Result<Captured> {
+        let Prepared {
+            input_path, format, ..
+        } = *prepared;
+        let family = self.family(input_path, prepared.inspection.format);
+        let (filter, filter_options) = format.export_filter(family);
+        let filter_data = if format == OutputFormat::Pdf && !self.pdf_options.is_default() {
+            self.pdf_options.validate()?;
+            Some(self.pdf_options.filter_options())
+        } else {
+            None
+        };
+        let job = uno::Job {
+            input: input_path,
+            output: &prepared.generated,
+            filter,
+            filter_options,
+            filter_data,
+            password: self.input_password.as_ref().map(Secret::expose),
+        };
+        let captured = match runner {
+            Runner::Pool(pool) => pool.run(&job, self.timeout)?,
+            Runner::Profile(profile) => {
+                let program = self.program_path();
+                let python = locate::resolve_python(self.python.as_deref(), &program);
+                uno::convert_once(&program, &python, profile, &job, self.timeout)?
+            }
+        };
+        if captured.status.and_then(|status| status.code()) == Some(uno::EXIT_PASSWORD) {
+            return Err(DittoError::EncryptedInput {
+                path: input_path.to_path_buf(),
+                password_given: self.input_password.is_some(),
+            });
+        }
+        Ok(captured)
+    }
+
+    /// The `soffice --convert-to` command for `prepared`, and the program it
+    /// runs.
+    pub(crate) fn cli_command(
+        &self,
+        prepared: &Prepared<'_>,
+        profile: &Profile,
+    ) -> Result<(Command, PathBuf)> {
+        let Prepared {
+            input_path, format, ..
+        } = *prepared;
+        let target = self.convert_to_arg(input_path, prepared.inspection.format, format)?;
+        let program = self.program_path();
+        let mut command = Command::new(&program);
+        command.args(profile.argument());
+        command.args(["--headless", "--convert-to", &target, "--outdir"]);
+        // Paths are passed as they are, so any name the OS allows works.
+        command.arg(prepared.staging.path());
+        command.arg(input_path);
+        Ok((command, program))
+    }
+
+    /// Turns how LibreOffice ended into the result of the conversion, moving
+    /// the converted file into place.
+    pub(crate) fn finish(
+        &self,
+        prepared: Prepared<'_>,
+        captured: Captured,
+    ) -> Result<ConversionReport> {
+        let Captured {
+            status,
+            stdout,
+            stderr,
+        } = captured;
+        match status {
+            None => {
+                return Err(DittoError::Timeout {
+                    timeout: self.timeout.unwrap_or_default(),
+                    stdout,
+                    stderr,
+                })
+            }
+            Some(status) if !status.success() => {
+                return Err(DittoError::ConverterFailed {
+                    code: status.code(),
+                    stdout,
+                    stderr,
+                })
+            }
+            Some(_) => {}
+        }
+
+        let Prepared {
+            output_path,
+            generated,
+            ..
+        } = prepared;
+        if !generated.exists() {
+            return Err(DittoError::OutputMissing {
+                path: output_path.to_path_buf(),
+                stdout,
+                stderr,
+            });
+        }
+
+        persist::persist(&generated, output_path, !self.no_overwrite)?;
+
+        Ok(ConversionReport {
+            output_path: output_path.to_path_buf(),
+            stdout,
+            stderr,
+            attempts: 1,
+        })
+    }
+}
+
+/// What runs LibreOffice for a conversion.
+#[derive(Debug, Clone, Copy)]
+enum Runner<'a> {
+    /// A new `soffice` process with this profile.
+    Profile(&'a Profile),
+    /// A worker of the pool.
+    Pool(&'a WorkerPool),
+}
+
+/// A conversion whose input has been checked, ready to run.
+#[derive(Debug)]
+pub(crate) struct Prepared<'a> {
+    input_path: &'a Path,
+    output_path: &'a Path,
+    format: OutputFormat,
+    inspection: Inspection,
+    /// Removed, with anything left in it, when the conversion is over.
+    staging: TempDir,
+    /// Where LibreOffice writes the converted file, inside `staging`.
+    generated: PathBuf,
+}
+
+impl Converter {
+    /// Converts many documents into `format` with as few LibreOffice runs as
+    /// possible, writing `<output_dir>/<input stem>.<extension>` for each.
+    ///
+    /// Starting LibreOffice takes seconds, so inputs are handed to a single
+    /// `soffice` invocation (one per distinct export filter when PDF options
+    /// are set, as the filter depends on the kind of document). Each input
+    /// gets its own entry in the returned list, in input order: a bad
+    /// document fails only its own entry. Inputs whose output name another
+    /// input already claimed fail with [`DittoError::OutputConflict`], and
+    /// encrypted inputs (when a password is set) and encrypted PDFs are
+    /// converted one at a time over UNO.
+    ///
+    /// The whole batch fails only when the output directory cannot be
+    /// created, the options are invalid, or LibreOffice cannot be started.
+    /// A configured timeout applies to each LibreOffice run; when it
+    /// expires, every input of that run fails with [`DittoError::Timeout`].
+    pub fn convert_batch<P: AsRef<Path>>(
+        &self,
+        inputs: &[P],
+        output_dir: &Path,
+        format: OutputFormat,
+    ) -> Result<Vec<BatchResult>> {
+        if format == OutputFormat::Pdf && !self.pdf_options.is_default() {
+            self.pdf_options.validate()?;
+        }
+        std::fs::create_dir_all(output_dir)?;
+
+        let mut results: Vec<Option<Result<ConversionReport>>> =
+            inputs.iter().map(|_| None).collect();
+        // Inputs still to convert, grouped by their `--convert-to` argument.
+        let mut runs: Vec<(String, Vec<usize>)> = Vec::new();
+        let mut claimed = HashSet::new();
+        for (index, input_path) in inputs.iter().map(AsRef::as_ref).enumerate() {
+            let planned = check_input_path(input_path)
+                .and_then(|()| converted_name(input_path, format))
+                .and_then(|name| {
+                    let output_path = output_dir.join(&name);
+                    check_not_input(input_path, &output_path)?;
+                    if !claimed.insert(name) {
+                        return Err(DittoError::OutputConflict(output_path));
+                    }
+                    if self.no_overwrite && output_path.exists() {
+                        return Err(DittoError::OutputExists(output_path));
+                    }
+                    let inspection = self.inspect(input_path, format)?;
+                    if inspection.encrypted || self.encrypts_pdf(format) {
+                        return Ok(Planned::Alone(output_path));
+                    }
+                    let target = self.convert_to_arg(input_path, inspection.format, format)?;
+                    Ok(Planned::Batched(target))
+                });
+            match planned {
+                Ok(Planned::Batched(target)) => {
+                    match runs.iter_mut().find(|(run, _)| *run == target) {
+                        Some((_, indices)) => indices.push(index),
+                        None => runs.push((target, vec![index])),
+                    }
+                }
+                // Passwords cannot be passed to `--convert-to` (or must not).
+                Ok(Planned::Alone(output_path)) => {
+                    results[index] = Some(self.convert(input_path, &output_path, format));
+                }
+                Err(error) => results[index] = Some(Err(error)),
+            }
+        }
+
+        for (target, indices) in runs {
+            let run_inputs: Vec<&Path> = indices.iter().map(|&i| inputs[i].as_ref()).collect();
+            let run_results = self.run_batch(&target, &run_inputs, output_dir, format)?;
+            for (index, result) in indices.into_iter().zip(run_results) {
+                results[index] = Some(result);
+            }
+        }
+
+        Ok(inputs
+            .iter()
+            .zip(results)
+            .map(|(input_path, result)| BatchResult {
+                input_path: input_path.as_ref().to_path_buf(),
+                result: result.expect("every input is planned"),
+            })
+            .collect())
+    }
+
+    /// Converts `inputs` in one `soffice` run, returning a result per input.
+    ///
+    /// LibreOffice writes into a staging directory of its own, so that only
+    /// files produced by this run are picked up.
+    fn run_batch(
+        &self,
+        target: &str,
+        inputs: &[&Path],
+        output_dir: &Path,
+        format: OutputFormat,
+    ) -> Result<Vec<Result<ConversionReport>>> {
+        let staging = self.staging_dir(output_dir)?;
+        let profile = Profile::prepare(&self.profile)?;
+        let program = self.program_path();
+        let mut command = Command::new(&program);
+        command.args(profile.argument());
+        command.args(["--headless", "--convert-to", target, "--outdir"]);
+        command.arg(staging.path());
+        command.args(inputs);
+
+        let Captured {
+            status,
+            stdout,
+            stderr,
+        } = process::run(command, self.timeout)
+            .map_err(|source| DittoError::from_spawn(program, source))?;
+
+        Ok(inputs
+            .iter()
+            .map(|input_path| {
+                let name = converted_name(input_path, format)?;
+                let generated = staging.path().join(&name);
+                match status {
+                    None => Err(DittoError::Timeout {
+                        timeout: self.timeout.unwrap_or_default(),
+                        stdout: stdout.clone(),
+                        stderr: stderr.clone(),
+                    }),
+                    Some(_) if generated.exists() => {
+                        let output_path = output_dir.join(&name);
+                        persist::persist(&generated, &output_path, !self.no_overwrite)?;
+                        Ok(ConversionReport {
+                            output_path,
+                            stdout: stdout.clone(),
+                            stderr: stderr.clone(),
+                            attempts: 1,
+                        })
+                    }
+                    Some(status) if !status.success() => Err(DittoError::ConverterFailed {
+                        code: status.code(),
+                        stdout: stdout.clone(),
+                        stderr: stderr.clone(),
+                    }),
+                    Some(_) => Err(DittoError::OutputMissing {
+                        path: output_dir.join(&name),
+                        stdout: stdout.clone(),
+                        stderr: stderr.clone(),
+                    }),
+                }
+            })
+            .collect())
+    }
+
+    /// Creates a private directory for LibreOffice to write into: in the
+    /// scratch directory if one is set, else in `output_dir`.
+    fn staging_dir(&self, output_dir: &Path) -> Result<TempDir> {
+        let parent = self.scratch_dir.as_deref().unwrap_or(output_dir);
+        std::fs::create_dir_all(parent)?;
+        Ok(tempfile::Builder::new()
+            .prefix(".ditto-")
+            .tempdir_in(parent)?)
+    }
+
+    /// Runs the input format check, unless turned off.
+    ///
+    /// Encrypted inputs are rejected here when no password is set.
+    fn inspect(&self, input_path: &Path, format: OutputFormat) -> Result<Inspection> {
+        if self.skip_input_check {
+            return Ok(Inspection::default());
+        }
+        let inspection = inspect_input(input_path, format)?;
+        if inspection.encrypted && self.input_password.is_none() {
+            return Err(DittoError::EncryptedInput {
+                path: input_path.to_path_buf(),
+                password_given: false,
+            });
+        }
+        Ok(inspection)
+    }
+
+    /// The `--convert-to` argument for converting `input_path` to `format`.
+    fn convert_to_arg(
+        &self,
+        input_path: &Path,
+        detected: Option<InputFormat>,
+        format: OutputFormat,
+    ) -> Result<String> {
+        if format != OutputFormat::Pdf || self.pdf_options.is_default() {
+            return Ok(format.convert_to_arg());
+        }
+        self.pdf_options.validate()?;
+        // The export filter must match the application that opens the input.
+        Ok(self
+            .pdf_options
+            .convert_to_arg(self.family(input_path, detected)))
+    }
+
+    /// The application LibreOffice opens the input with, from the detected
+    /// format or else the extension.
+    fn family(&self, input_path: &Path, detected: Option<InputFormat>) -> DocumentFamily {
+        detected
+            .or_else(|| extension_format(input_path))
+            .map_or(DocumentFamily::Text, InputFormat::family)
+    }
+}
+
+/// How an input of a batch gets converted.
+enum Planned {
+    /// In the `soffice` run using this `--convert-to` argument.
+    Batched(String),
+    /// On its own, into this output path.
+    Alone(PathBuf),
+}
+
+/// Checks that the input exists and is a regular file.
+fn check_input_path(input_path: &Path) -> Result<()> {
+    if !input_path.exists() {
+        return Err(DittoError::InputNotFound(input_path.to_path_buf()));
+    }
+    if !input_path.is_file() {
+        return Err(DittoError::InputNotAFile(input_path.to_path_buf()));
+    }
+    Ok(())
+}
+
+/// The file name LibreOffice gives the converted input: its stem with the
+/// extension of `format` (`report.v2.docx` becomes `report.v2.pdf`).
+fn converted_name(input_path: &Path, format: OutputFormat) -> Result<OsString> {
+    let mut name = input_path
+        .file_stem()
+        .ok_or_else(|| DittoError::InputNotAFile(input_path.to_path_buf()))?
+        .to_os_string();
+    name.push(".");
+    name.push(format.extension());
+    Ok(name)
+}
+
+/// The document format the input's extension claims, if it names one.
+fn extension_format(input_path: &Path) -> Option<InputFormat> {
+    input_path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .and_then(InputFormat::from_extension)
+}
+
+/// Checks that writing `output_path` would not replace the input, however
+/// either path is spelled.
+fn check_not_input(input_path: &Path, output_path: &Path) -> Result<()> {
+    if output_path.exists()
+        && std::fs::canonicalize(input_path)? == std::fs::canonicalize(output_path)?
+    {
+        return Err(DittoError::OutputIsInput(output_path.to_path_buf()));
+    }
+    Ok(())
+}
+
+/// What [`inspect_input`] found out about an input.
+#[derive(Debug, Default)]
+struct Inspection {
+    /// The detected format, if any.
+    format: Option<InputFormat>,
+    /// Whether the input is a password-protected document.
+    encrypted: bool,
+}
+
+/// Identifies the input and checks it can be converted to `format`.
+fn inspect_input(input_path: &Path, format: OutputFormat) -> Result<Inspection> {
+    let mut file = File::open(input_path)?;
+    let expected = extension_format(input_path);
+
+    if sniff::is_encrypted(&mut file)? {
+        // The contents are hidden in the encrypted package, so the extension
+        // is all there is to go on.
+        let format_hint = expected.filter(|expected| {
+            matches!(
+                expected,
+                InputFormat::Docx | InputFormat::Xlsx | InputFormat::Pptx
+            )
+        });
+        if let Some(from) = format_hint.filter(|from| !from.family().can_export_to(format)) {
+            return Err(DittoError::UnsupportedConversion { from, to: format });
+        }
+        return Ok(Inspection {
+            format: format_hint,
+            encrypted: true,
+        });
+    }
+
+    let detected = sniff::detect(&mut file)?;
+    match (detected, expected) {
+        (None, Some(expected)) => Err(DittoError::UnrecognizedInput {
+            path: input_path.to_path_buf(),
+            expected,
+        }),
+        (Some(detected), _) if !detected.family().can_export_to(format) => {
+            Err(DittoError::UnsupportedConversion {
+                from: detected,
+                to: format,
+            })
+        }
+        _ => Ok(Inspection {
+            format: detected,
+            encrypted: false,
+        }),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::test_support::{
+        fake_program, fake_soffice, ole2_with_streams, write_input, CONVERTING_SCRIPT,
+        UNO_HELPER_SCRIPT,
+    };
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_docx_to_pdf_with_fake_soffice() {
+        let temp_dir = tempdir().unwrap();
+        let soffice = fake_soffice(temp_dir.path(), CONVERTING_SCRIPT);
+        let input = write_input(temp_dir.path(), "report.docx");
+        let output = temp_dir.path().join("out").join("final.pdf");
+
+        let report = Converter::new()
+            .program(&soffice)
+            .docx_to_pdf(&input, &output)
+            .unwrap();
+
+        assert_eq!(report.output_path, output);
+        assert!(report.stdout.starts_with("convert "));
+        let pdf = std::fs::read_to_string(&output).unwrap();
+        assert_eq!(pdf, "%PDF-1.4 converted from report.docx\n");
+    }
+
+    #[test]
+    fn test_convert_passes_format_and_renames_output() {
+        let temp_dir = tempdir().unwrap();
+        let soffice = fake_soffice(temp_dir.path(), CONVERTING_SCRIPT);
+        let input = write_input(temp_dir.path(), "sheet.xlsx");
+        let output = temp_dir.path().join("sheet-export.csv");
+
+        Converter::new()
+            .program(&soffice)
+            .convert(&input, &output, OutputFormat::Csv)
+            .unwrap();
+
+        assert!(output.is_file());
+        let args = std::fs::read_to_string(temp_dir.path().join("args.txt")).unwrap();
+        assert!(args
+            .lines()
+            .any(|arg| arg == "csv:Text - txt - csv (StarCalc):44,34,76"));
+    }
+
+    #[test]
+    fn test_convert_rejects_inputs_before_launching_soffice() {
+        let temp_dir = tempdir().unwrap();
+        let soffice = fake_soffice(temp_dir.path(), CONVERTING_SCRIPT);
+        let converter = Converter::new().program(&soffice);
+
+        let fake_docx = temp_dir.path().join("notes.docx");
+        std::fs::write(&fake_docx, "Test content\n").unwrap();
+        let result = converter.docx_to_pdf(&fake_docx, &temp_dir.path().join("notes.pdf"));
+        assert!(matches!(
+            result,
+            Err(DittoError::UnrecognizedInput {
+                expected: InputFormat::Docx,
+                ..
+            })
+        ));
+
+        let sheet = write_input(temp_dir.path(), "sheet.xlsx");
+        let result = converter.convert(&sheet, &temp_dir.path().join("s.docx"), OutputFormat::Docx);
+        assert!(matches!(
+            result,
+            Err(DittoError::UnsupportedConversion { .. })
+        ));
+        assert!(!temp_dir.path().join("args.txt").exists());
+
+        // Formats without a signature are left to LibreOffice.
+        let csv = temp_dir.path().join("table.csv");
+        std::fs::write(&csv, "a,b\n1,2\n").unwrap();
+        converter
+            .convert(&csv, &temp_dir.path().join("table.pdf"), OutputFormat::Pdf)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_pdf_options_select_filter_for_input_family() {
+        let temp_dir = tempdir().unwrap();
+        let soffice = fake_soffice(temp_dir.path(), CONVERTING_SCRIPT);
+        let input = write_input(temp_dir.path(), "deck.pptx");
+
+        Converter::new()
+            .program(&soffice)
+            .pdf_options(PdfExportOptions::new().pdfa(crate::PdfA::A2b))
+            .convert(&input, &temp_dir.path().join("deck.pdf"), OutputFormat::Pdf)
+            .unwrap();
+
+        let args = std::fs::read_to_string(temp_dir.path().join("args.txt")).unwrap();
+        assert!(args.lines().any(|arg| {
+            arg
+            == "pdf:impress_pdf_Export:{\"SelectPdfVersion\":{\"type\":\"long\",\"value\":\"2\"}}"
+        }));
+
+        let result = Converter::new()
+            .program(&soffice)
+            .pdf_options(PdfExportOptions::new().jpeg_quality(101))
+            .convert(&input, &temp_dir.path().join("deck.pdf"), OutputFormat::Pdf);
+        assert!(matches!(result, Err(DittoError::InvalidOptions(_))));
+    }
+
+    #[test]
+    fn test_pdf_passwords_stay_off_command_lines() {
+        let temp_dir = tempdir().unwrap();
+        let record = r#"here=$(dirname "$0")
+printf '%s\n' "$@" >> "$here/argv.txt"
+"#;
+        let soffice = fake_soffice(temp_dir.path(), &format!("{}{}", record, CONVERTING_SCRIPT));
+        let python = fake_program(
+            temp_dir.path(),
+            "python",
+            &format!(
+                r#"{}printf '%s' "$DITTO_FILTER_DATA" > "$here/filter-data.txt"
+while [ $# -gt 0 ]; do
+  case "$1" in
+    --output) output="${{2#file://}}"; shift ;;
+  esac
+  shift
+done
+echo converted > "$output"
+"#,
+                record
+            ),
+        );
+        let inputs = [
+            write_input(temp_dir.path(), "a.docx"),
+            write_input(temp_dir.path(), "b.docx"),
+        ];
+        let converter = Converter::new()
+            .program(&soffice)
+            .python(&python)
+            .pdf_options(
+                PdfExportOptions::new()
+                    .open_password("open-sesame")
+                    .owner_password("owner-sesame"),
+            );
+
+        converter
+            .docx_to_pdf(&inputs[0], &temp_dir.path().join("a.pdf"))
+            .unwrap();
+        let results = converter
+            .clone()
+            .convert_batch(&inputs, &temp_dir.path().join("batch"), OutputFormat::Pdf)
+            .unwrap();
+        assert!(results.iter().all(|item| item.result.is_ok()));
+
+        let argv = std::fs::read_to_string(temp_dir.path().join("argv.txt")).unwrap();
+        assert!(argv.contains("--accept="), "not converted over UNO");
+        assert!(!argv.contains("sesame"), "password on a command line");
+        let filter_data = std::fs::read_to_string(temp_dir.path().join("filter-data.txt")).unwrap();
+        assert!(filter_data
+            .contains("\"DocumentOpenPassword\":{\"type\":\"string\",\"value\":\"open-sesame\"}"));
+        assert!(filter_data.contains("owner-sesame"));
+    }
+
+    #[test]
+    fn test_docx_to_pdf_reports_converter_failure() {
+        let temp_dir = tempdir().unwrap();
+        let soffice = fake_soffice(temp_dir.path(), "echo 'Error: boom' >&2\nexit 7\n");
+        let input = write_input(temp_dir.path(), "report.docx");
+
+        let result = Converter::new()
+            .program(&soffice)
+            .docx_to_pdf(&input, &temp_dir.path().join("report.pdf"));
+
+        match result {
+            Err(DittoError::ConverterFailed { code, stderr, .. }) => {
+                assert_eq!(code, Some(7));
+                assert_eq!(stderr, "Error: boom\n");
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_docx_to_pdf_times_out() {
+        let temp_dir = tempdir().unwrap();
+        let soffice = fake_soffice(temp_dir.path(), "sleep 30\n");
+        let input = write_input(temp_dir.path(), "report.docx");
+
+        let result = Converter::new()
+            .program(&soffice)
+            .timeout(Duration::from_millis(200))
+            .docx_to_pdf(&input, &temp_dir.path().join("report.pdf"));
+
+        assert!(matches!(result, Err(DittoError::Timeout { .. })));
+    }
+
+    #[test]
+    fn test_docx_to_pdf_missing_program() {
+        let temp_dir = tempdir().unwrap();
+        let input = write_input(temp_dir.path(), "report.docx");
+        let missing = temp_dir.path().join("no-such-soffice");
+
+        let result = Converter::new()
+            .program(&missing)
+            .docx_to_pdf(&input, &temp_dir.path().join("report.pdf"));
+
+        match result {
+            Err(DittoError::ConverterNotFound { program, .. }) => assert_eq!(program, missing),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_docx_to_pdf_passes_isolated_profile() {
+        let temp_dir = tempdir().unwrap();
+        let soffice = fake_soffice(temp_dir.path(), CONVERTING_SCRIPT);
+        let input = write_input(temp_dir.path(), "report.docx");
+
+        Converter::new()
+            .program(&soffice)
+            .docx_to_pdf(&input, &temp_dir.path().join("report.pdf"))
+            .unwrap();
+
+        let args = std::fs::read_to_string(temp_dir.path().join("args.txt")).unwrap();
+        let profile_arg = args.lines().next().unwrap();
+        let url = profile_arg
+            .strip_prefix("-env:UserInstallation=file://")
+            .unwrap();
+        assert!(
+            !Path::new(url).exists(),
+            "temporary profile was not removed"
+        );
+    }
+
+    #[test]
+    fn test_encrypted_input_needs_the_right_password() {
+        let temp_dir = tempdir().unwrap();
+        let soffice = fake_soffice(temp_dir.path(), "exit 0\n");
+        let python = fake_program(temp_dir.path(), "python", UNO_HELPER_SCRIPT);
+        let input = temp_dir.path().join("locked.docx");
+        std::fs::write(
+            &input,
+            ole2_with_streams(&["EncryptionInfo", "EncryptedPackage"]),
+        )
+        .unwrap();
+        let output = temp_dir.path().join("locked.pdf");
+        let converter = Converter::new().program(&soffice).python(&python);
+
+        let result = converter.docx_to_pdf(&input, &output);
+        assert!(matches!(
+            result,
+            Err(DittoError::EncryptedInput {
+                password_given: false,
+                ..
+            })
+        ));
+
+        let result = converter
+            .clone()
+            .input_password("wrong")
+            .docx_to_pdf(&input, &output);
+        assert!(matches!(
+            result,
+            Err(DittoError::EncryptedInput {
+                password_given: true,
+                ..
+            })
+        ));
+
+        converter
+            .input_password("secret")
+            .docx_to_pdf(&input, &output)
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&output).unwrap(),
+            "writer_pdf_Export\n"
+        );
+    }
+
+    #[test]
+    fn test_convert_batch_reports_each_input() {
+        let temp_dir = tempdir().unwrap();
+        let soffice = fake_soffice(temp_dir.path(), CONVERTING_SCRIPT);
+        let first = write_input(temp_dir.path(), "report.docx");
+        std::fs::create_dir(temp_dir.path().join("sub")).unwrap();
+        let same_name = write_input(&temp_dir.path().join("sub"), "report.docx");
+        let broken = write_input(temp_dir.path(), "broken.docx");
+        let missing = temp_dir.path().join("missing.docx");
+        let deck = write_input(temp_dir.path(), "deck.pptx");
+        let out = temp_dir.path().join("out");
+
+        let results = Converter::new()
+            .program(&soffice)
+            .convert_batch(
+                &[&first, &same_name, &broken, &missing, &deck],
+                &out,
+                OutputFormat::Pdf,
+            )
+            .unwrap();
+
+        let inputs: Vec<_> = results.iter().map(|item| &item.input_path).collect();
+        assert_eq!(inputs, [&first, &same_name, &broken, &missing, &deck]);
+        assert_eq!(
+            results[0].result.as_ref().unwrap().output_path,
+            out.join("report.pdf")
+        );
+        assert!(matches!(
+            results[1].result,
+            Err(DittoError::OutputConflict(_))
+        ));
+        match &results[2].result {
+            Err(DittoError::OutputMissing { path, stderr, .. }) => {
+                assert_eq!(path, &out.join("broken.pdf"));
+                assert_eq!(stderr, "Error: source file could not be loaded\n");
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(matches!(
+            results[3].result,
+            Err(DittoError::InputNotFound(_))
+        ));
+        assert!(results[4].result.is_ok());
+
+        // One LibreOffice run for everything, and no staging directory left.
+        let args = std::fs::read_to_string(temp_dir.path().join("args.txt")).unwrap();
+        assert_eq!(args.lines().filter(|arg| arg.ends_with(".docx")).count(), 2);
+        assert!(args.lines().any(|arg| arg.ends_with("deck.pptx")));
+        let mut written: Vec<_> = std::fs::read_dir(&out)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        written.sort();
+        assert_eq!(written, ["deck.pdf", "report.pdf"]);
+    }
+
+    #[test]
+    fn test_failed_conversion_leaves_no_partial_output() {
+        let temp_dir = tempdir().unwrap();
+        let soffice = fake_soffice(
+            temp_dir.path(),
+            "while [ $# -gt 1 ]; do [ \"$1\" = --outdir ] && outdir=$2; shift; done\n\
+             printf '%%PDF-1.4 trunc' > \"$outdir/report.pdf\"\nexit 1\n",
+        );
+        let input = write_input(temp_dir.path(), "report.docx");
+        let out = temp_dir.path().join("out");
+
+        let result = Converter::new()
+            .program(&soffice)
+            .docx_to_pdf(&input, &out.join("report.pdf"));
+
+        assert!(matches!(result, Err(DittoError::ConverterFailed { .. })));
+        assert_eq!(std::fs::read_dir(&out).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_overwrite_false_keeps_existing_output() {
+        let temp_dir = tempdir().unwrap();
+        let soffice = fake_soffice(temp_dir.path(), CONVERTING_SCRIPT);
+        let input = write_input(temp_dir.path(), "report.docx");
+        let output = temp_dir.path().join("report.pdf");
+        std::fs::write(&output, "keep me").unwrap();
+
+        let result = Converter::new()
+            .program(&soffice)
+            .overwrite(false)
+            .docx_to_pdf(&input, &output);
+
+        assert!(matches!(result, Err(DittoError::OutputExists(_))));
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "keep me");
+        assert!(!temp_dir.path().join("args.txt").exists());
+    }
+
+    #[test]
+    fn test_unrelated_file_with_same_stem_is_left_alone() {
+        let temp_dir = tempdir().unwrap();
+        let input = write_input(temp_dir.path(), "report.docx");
+        let out = temp_dir.path().join("out");
+        std::fs::create_dir(&out).unwrap();
+        let unrelated = out.join("report.pdf");
+        std::fs::write(&unrelated, "someone else's report").unwrap();
+
+        // LibreOffice "succeeds" without writing anything: the stale
+        // report.pdf must not be mistaken for its output.
+        let silent = fake_soffice(temp_dir.path(), "exit 0\n");
+        let result = Converter::new()
+            .program(&silent)
+            .docx_to_pdf(&input, &out.join("final.pdf"));
+        assert!(matches!(result, Err(DittoError::OutputMissing { .. })));
+        assert!(!out.join("final.pdf").exists());
+
+        let soffice = fake_soffice(temp_dir.path(), CONVERTING_SCRIPT);
+        Converter::new()
+            .program(&soffice)
+            .docx_to_pdf(&input, &out.join("final.pdf"))
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&unrelated).unwrap(),
+            "someone else's report"
+        );
+        assert!(out.join("final.pdf").is_file());
+    }
+
+    #[test]
+    fn test_concurrent_conversions_with_the_same_stem() {
+        let temp_dir = tempdir().unwrap();
+        // Writes the full input path, and gives the other job time to
+        // produce its file in the meantime.
+        let soffice = fake_soffice(
+            temp_dir.path(),
+            "while [ $# -gt 1 ]; do [ \"$1\" = --outdir ] && outdir=$2; shift; done\n\
+             sleep 0.2\necho \"$1\" > \"$outdir/report.pdf\"\n",
+        );
+        let out = temp_dir.path().join("out");
+        let jobs: Vec<_> = ["a", "b"]
+            .into_iter()
+            .map(|name| {
+                let dir = temp_dir.path().join(name);
+                std::fs::create_dir(&dir).unwrap();
+                (
+                    write_input(&dir, "report.docx"),
+                    out.join(format!("{}.pdf", name)),
+                )
+            })
+            .collect();
+
+        std::thread::scope(|scope| {
+            for (input, output) in &jobs {
+                let converter = Converter::new().program(&soffice);
+                scope.spawn(move || converter.docx_to_pdf(input, output).unwrap());
+            }
+        });
+
+        for (input, output) in &jobs {
+            assert_eq!(
+                std::fs::read_to_string(output).unwrap().trim_end(),
+                input.to_str().unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_scratch_dir_is_used_and_emptied() {
+        let temp_dir = tempdir().unwrap();
+        let soffice = fake_soffice(temp_dir.path(), CONVERTING_SCRIPT);
+        let input = write_input(temp_dir.path(), "report.docx");
+        let scratch = temp_dir.path().join("scratch");
+        let output = temp_dir.path().join("out").join("report.pdf");
+
+        Converter::new()
+            .program(&soffice)
+            .scratch_dir(&scratch)
+            .docx_to_pdf(&input, &output)
+            .unwrap();
+
+        assert!(output.is_file());
+        let args = std::fs::read_to_string(temp_dir.path().join("args.txt")).unwrap();
+        assert!(args
+            .lines()
+            .any(|arg| arg.starts_with(scratch.to_str().unwrap())));
+        assert_eq!(std::fs::read_dir(&scratch).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_non_utf8_paths_are_passed_through() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+        let temp_dir = tempdir().unwrap();
+        let soffice = fake_soffice(temp_dir.path(), CONVERTING_SCRIPT);
+        let dir = temp_dir.path().join(OsStr::from_bytes(b"d\xe9p\xf4t"));
+        std::fs::create_dir(&dir).unwrap();
+        let input = dir.join(OsStr::from_bytes(b"r\xe9sum\xe9.docx"));
+        std::fs::rename(write_input(temp_dir.path(), "report.docx"), &input).unwrap();
+
+        let converter = Converter::new().program(&soffice);
+        converter
+            .convert(&input, &input.with_extension("pdf"), OutputFormat::Pdf)
+            .unwrap();
+        let pdf = std::fs::read(dir.join(OsStr::from_bytes(b"r\xe9sum\xe9.pdf"))).unwrap();
+        assert_eq!(pdf, b"%PDF-1.4 converted from r\xe9sum\xe9.docx\n");
+
+        let results = converter
+            .convert_batch(&[&input], &dir.join("out"), OutputFormat::Odt)
+            .unwrap();
+        assert!(results[0].result.is_ok());
+        assert!(dir
+            .join("out")
+            .join(OsStr::from_bytes(b"r\xe9sum\xe9.odt"))
+            .is_file());
+    }
+}
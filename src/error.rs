@@ -0,0 +1,376 @@
+//! Error type returned by the conversion functions.
+
+use crate::format::OutputFormat;
+use crate::sniff::InputFormat;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// A specialized `Result` type for ditto operations.
+pub type Result<T> = std::result::Result<T, DittoError>;
+
+/// Everything that can go wrong while converting a document.
+///
+/// Each variant corresponds to one failure class, so callers can decide
+/// (for example) whether a conversion is worth retrying without inspecting
+/// the error message. New variants may be added; [`DittoError::kind`]
+/// classifies every one of them.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum DittoError {
+    /// The input path does not exist.
+    InputNotFound(PathBuf),
+    /// The input path exists but is not a regular file.
+    InputNotAFile(PathBuf),
+    /// The output path points to an existing directory.
+    OutputIsDirectory(PathBuf),
+    /// The output path names the input file itself, which the conversion
+    /// would replace.
+    OutputIsInput(PathBuf),
+    /// The input's extension names a document format, but its contents are
+    /// not a document of that format (or of any format we recognise).
+    UnrecognizedInput {
+        /// The input file.
+        path: PathBuf,
+        /// The format its extension claims.
+        expected: InputFormat,
+    },
+    /// The input is a kind of document LibreOffice cannot export to the
+    /// requested format (for example a spreadsheet to DOCX).
+    UnsupportedConversion {
+        /// The detected input format.
+        from: InputFormat,
+        /// The requested output format.
+        to: OutputFormat,
+    },
+    /// The input document is encrypted and no password, or a wrong one, was
+    /// given.
+    EncryptedInput {
+        /// The input file.
+        path: PathBuf,
+        /// Whether a password was supplied (and rejected).
+        password_given: bool,
+    },
+    /// An in-memory or streamed input exceeds the configured maximum size.
+    InputTooLarge {
+        /// The maximum size in bytes.
+        limit: u64,
+    },
+    /// The output file already exists and overwriting it is not allowed.
+    OutputExists(PathBuf),
+    /// The converted file could not be moved or copied to the output path.
+    OutputNotWritten {
+        /// The output path.
+        path: PathBuf,
+        /// The underlying error.
+        source: io::Error,
+    },
+    /// Another input of a batch is already converted to this output path.
+    OutputConflict(PathBuf),
+    /// A conversion option holds a value LibreOffice does not accept.
+    InvalidOptions(String),
+    /// The converter executable could not be found or started.
+    ConverterNotFound {
+        /// The program that was launched.
+        program: PathBuf,
+        /// The error reported when spawning the process.
+        source: io::Error,
+    },
+    /// The converter ran but exited unsuccessfully.
+    ConverterFailed {
+        /// The exit code, or `None` if the process was killed by a signal.
+        code: Option<i32>,
+        /// Whatever the converter printed to its standard output.
+        stdout: String,
+        /// Whatever the converter printed to its standard error.
+        stderr: String,
+    },
+    /// The converter did not finish within the configured timeout and was killed.
+    Timeout {
+        /// The timeout that was exceeded.
+        timeout: Duration,
+        /// Whatever the converter printed to its standard output before it was killed.
+        stdout: String,
+        /// Whatever the converter printed to its standard error before it was killed.
+        stderr: String,
+    },
+    /// The conversion was cancelled through its
+    /// [`JobHandle`](crate::JobHandle) before it finished.
+    Cancelled,
+    /// The converter reported success but the expected file was not produced.
+    ///
+    /// LibreOffice exits with status 0 when it cannot load a document, so the
+    /// captured output is usually the only hint of what went wrong.
+    OutputMissing {
+        /// Where the converted file was expected.
+        path: PathBuf,
+        /// Whatever the converter printed to its standard output.
+        stdout: String,
+        /// Whatever the converter printed to its standard error.
+        stderr: String,
+    },
+    /// Any other I/O failure (creating directories, moving files, ...).
+    Io(io::Error),
+}
+
+/// The failure class of a [`DittoError`], as returned by
+/// [`DittoError::kind`].
+///
+/// Frontends that report failures by class (an exit code, a status, an
+/// exception type) rather than by individual variant match on this.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// The input is missing, unreadable or not a document that can be
+    /// converted as asked.
+    Input,
+    /// The output cannot be written where it was asked to go.
+    Output,
+    /// The conversion options are invalid, or the output would replace the
+    /// input.
+    Usage,
+    /// LibreOffice cannot be started.
+    Unavailable,
+    /// LibreOffice did not finish in time.
+    Timeout,
+    /// The conversion itself failed, or was cancelled.
+    Failed,
+}
+
+impl fmt::Display for DittoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DittoError::InputNotFound(path) => {
+                write!(f, "Input file not found: {}", path.display())
+            }
+            DittoError::InputNotAFile(path) => {
+                write!(f, "Input path is not a file: {}", path.display())
+            }
+            DittoError::OutputIsDirectory(path) => {
+                write!(f, "Output path is a directory: {}", path.display())
+            }
+            DittoError::OutputIsInput(path) => {
+                write!(f, "Output path is the input file: {}", path.display())
+            }
+            DittoError::UnrecognizedInput { path, expected } => write!(
+                f,
+                "Input file is not a valid {} document: {}",
+                expected,
+                path.display()
+            ),
+            DittoError::UnsupportedConversion { from, to } => {
+                write!(f, "Cannot convert a {} document to {}", from, to)
+            }
+            DittoError::EncryptedInput {
+                path,
+                password_given: false,
+            } => write!(
+                f,
+                "Input document is password protected: {}",
+                path.display()
+            ),
+            DittoError::EncryptedInput {
+                path,
+                password_given: true,
+            } => write!(
+                f,
+                "Wrong password for encrypted input document: {}",
+                path.display()
+            ),
+            DittoError::InputTooLarge { limit } => {
+                write!(f, "Input is larger than the limit of {} bytes", limit)
+            }
+            DittoError::OutputExists(path) => {
+                write!(f, "Output file already exists: {}", path.display())
+            }
+            DittoError::OutputNotWritten { path, source } => {
+                write!(
+                    f,
+                    "Could not write output file {}: {}",
+                    path.display(),
+                    source
+                )
+            }
+            DittoError::OutputConflict(path) => write!(
+                f,
+                "Another input in the batch is converted to: {}",
+                path.display()
+            ),
+            DittoError::InvalidOptions(message) => {
+                write!(f, "Invalid conversion options: {}", message)
+            }
+            DittoError::ConverterNotFound { program, .. } => {
+                write!(f, "Converter could not be started: {}", program.display())
+            }
+            DittoError::ConverterFailed {
+                code,
+                stdout,
+                stderr,
+            } => {
+                match code {
+                    Some(code) => write!(f, "Conversion failed with exit code {}", code)?,
+                    None => write!(f, "Conversion failed: converter was terminated by a signal")?,
+                }
+                write_converter_output(f, stdout, stderr)
+            }
+            DittoError::Timeout {
+                timeout,
+                stdout,
+                stderr,
+            } => {
+                write!(f, "Conversion timed out after {:?}", timeout)?;
+                write_converter_output(f, stdout, stderr)
+            }
+            DittoError::Cancelled => write!(f, "Conversion was cancelled"),
+            DittoError::OutputMissing {
+                path,
+                stdout,
+                stderr,
+            } => {
+                write!(f, "Generated file not found at: {}", path.display())?;
+                write_converter_output(f, stdout, stderr)
+            }
+            DittoError::Io(e) => write!(f, "I/O error: {}", e),
+        }
+    }
+}
+
+/// Appends the most useful converter output to an error message.
+///
+/// Standard error is preferred; LibreOffice prints some load failures to
+/// standard output instead, so that is used as a fallback.
+fn write_converter_output(f: &mut fmt::Formatter<'_>, stdout: &str, stderr: &str) -> fmt::Result {
+    let text = match stderr.trim() {
+        "" => stdout.trim(),
+        stderr => stderr,
+    };
+    if text.is_empty() {
+        Ok(())
+    } else {
+        write!(f, ": {}", text)
+    }
+}
+
+impl std::error::Error for DittoError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DittoError::ConverterNotFound { source, .. }
+            | DittoError::OutputNotWritten { source, .. } => Some(source),
+            DittoError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl DittoError {
+    /// Classifies a failure to spawn `program`: a missing or non-executable
+    /// program is [`DittoError::ConverterNotFound`], anything else is I/O.
+    pub(crate) fn from_spawn(program: PathBuf, source: io::Error) -> Self {
+        match source.kind() {
+            io::ErrorKind::NotFound | io::ErrorKind::PermissionDenied => {
+                DittoError::ConverterNotFound { program, source }
+            }
+            _ => DittoError::Io(source),
+        }
+    }
+
+    /// The failure class this error belongs to.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            DittoError::InputNotFound(_)
+            | DittoError::InputNotAFile(_)
+            | DittoError::UnrecognizedInput { .. }
+            | DittoError::UnsupportedConversion { .. }
+            | DittoError::EncryptedInput { .. }
+            | DittoError::InputTooLarge { .. } => ErrorKind::Input,
+            DittoError::OutputIsDirectory(_)
+            | DittoError::OutputExists(_)
+            | DittoError::OutputNotWritten { .. }
+            | DittoError::OutputConflict(_) => ErrorKind::Output,
+            DittoError::InvalidOptions(_) | DittoError::OutputIsInput(_) => ErrorKind::Usage,
+            DittoError::ConverterNotFound { .. } => ErrorKind::Unavailable,
+            DittoError::Timeout { .. } => ErrorKind::Timeout,
+            DittoError::ConverterFailed { .. }
+            | DittoError::Cancelled
+            | DittoError::OutputMissing { .. }
+            | DittoError::Io(_) => ErrorKind::Failed,
+        }
+    }
+
+    /// Whether the same conversion might succeed if tried again.
+    ///
+    /// True for [`DittoError::ConverterFailed`] and
+    /// [`DittoError::OutputMissing`], which is how LibreOffice failing to
+    /// start up properly shows. A document it cannot load fails the same
+    /// way, so retrying does not always help. This is what a
+    /// [`RetryPolicy`](crate::RetryPolicy) retries by default.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            DittoError::ConverterFailed { .. } | DittoError::OutputMissing { .. }
+        )
+    }
+}
+
+impl From<io::Error> for DittoError {
+    fn from(e: io::Error) -> Self {
+        DittoError::Io(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error;
+
+    #[test]
+    fn test_io_error_is_exposed_as_source() {
+        let err = DittoError::from(io::Error::other("disk full"));
+        assert!(matches!(err, DittoError::Io(_)));
+        assert_eq!(err.source().unwrap().to_string(), "disk full");
+    }
+
+    #[test]
+    fn test_converter_failed_display_includes_stderr() {
+        let err = DittoError::ConverterFailed {
+            code: Some(1),
+            stdout: String::new(),
+            stderr: "Error: source file could not be loaded\n".to_string(),
+        };
+        assert_eq!(
+            err.to_string(),
+            "Conversion failed with exit code 1: Error: source file could not be loaded"
+        );
+        assert!(err.source().is_none());
+    }
+
+    #[test]
+    fn test_output_missing_display_falls_back_to_stdout() {
+        let err = DittoError::OutputMissing {
+            path: PathBuf::from("/tmp/out.pdf"),
+            stdout: "Error: source file could not be loaded\n".to_string(),
+            stderr: "  \n".to_string(),
+        };
+        assert_eq!(
+            err.to_string(),
+            "Generated file not found at: /tmp/out.pdf: Error: source file could not be loaded"
+        );
+    }
+
+    #[test]
+    fn test_kind_classifies_failures() {
+        assert_eq!(
+            DittoError::InputTooLarge { limit: 1 }.kind(),
+            ErrorKind::Input
+        );
+        assert_eq!(
+            DittoError::OutputExists(PathBuf::from("out.pdf")).kind(),
+            ErrorKind::Output
+        );
+        assert_eq!(
+            DittoError::InvalidOptions(String::new()).kind(),
+            ErrorKind::Usage
+        );
+        assert_eq!(DittoError::Cancelled.kind(), ErrorKind::Failed);
+    }
+}
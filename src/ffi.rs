@@ -0,0 +1,430 @@
+//! A C API over [`Converter`], for callers in other languages.
+//!
+//! Available with the `ffi` cargo feature, in the shared library built with
+//! `cargo rustc --lib --features ffi --crate-type cdylib`. The declarations
+//! are generated into `ditto.h` in the build's `OUT_DIR`, and a test checks
+//! that the copy in `include/` matches. Functions return a [`DittoStatus`],
+//! whose values match the exit codes of the `ditto` command, and keep the
+//! message of the last failure per thread for
+//! [`ditto_last_error_message`]. Strings are NUL-terminated; on Unix paths
+//! are taken as raw bytes, elsewhere they must be UTF-8.
+
+use crate::{Converter, DittoError, ErrorKind, OutputFormat};
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::ptr;
+use std::time::Duration;
+
+/// The outcome of a call.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DittoStatus {
+    /// The call succeeded.
+    Ok = 0,
+    /// LibreOffice failed to convert the document.
+    ConversionFailed = 1,
+    /// An argument was null, not a valid path or format, or an option was
+    /// rejected.
+    InvalidArgument = 2,
+    /// The input is missing, is not a supported document, or is password
+    /// protected.
+    InputError = 3,
+    /// The output path is invalid, or the output exists and overwriting is
+    /// disabled.
+    OutputError = 4,
+    /// LibreOffice (`soffice`) could not be started.
+    ConverterNotFound = 5,
+    /// The conversion did not finish within the timeout.
+    Timeout = 6,
+}
+
+impl From<&DittoError> for DittoStatus {
+    fn from(error: &DittoError) -> Self {
+        match error.kind() {
+            ErrorKind::Input => DittoStatus::InputError,
+            ErrorKind::Output => DittoStatus::OutputError,
+            ErrorKind::Usage => DittoStatus::InvalidArgument,
+            ErrorKind::Unavailable => DittoStatus::ConverterNotFound,
+            ErrorKind::Timeout => DittoStatus::Timeout,
+            ErrorKind::Failed => DittoStatus::ConversionFailed,
+        }
+    }
+}
+
+/// Conversion settings, created by `ditto_converter_new` and released
+/// with `ditto_converter_free`.
+pub struct DittoConverter {
+    converter: Converter,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// A failure to report through `ditto_last_error_message`.
+struct Failure {
+    status: DittoStatus,
+    message: String,
+}
+
+impl From<DittoError> for Failure {
+    fn from(error: DittoError) -> Self {
+        Failure {
+            status: DittoStatus::from(&error),
+            message: error.to_string(),
+        }
+    }
+}
+
+fn invalid(message: impl Into<String>) -> Failure {
+    Failure {
+        status: DittoStatus::InvalidArgument,
+        message: message.into(),
+    }
+}
+
+/// Runs `call`, recording its failure (or panic) as the thread's last error.
+fn record(call: impl FnOnce() -> Result<(), Failure>) -> DittoStatus {
+    let failure = match panic::catch_unwind(AssertUnwindSafe(call)) {
+        Ok(Ok(())) => {
+            set_last_error(None);
+            return DittoStatus::Ok;
+        }
+        Ok(Err(failure)) => failure,
+        Err(_) => Failure {
+            status: DittoStatus::ConversionFailed,
+            message: "ditto panicked".to_string(),
+        },
+    };
+    set_last_error(Some(failure.message));
+    failure.status
+}
+
+fn set_last_error(message: Option<String>) {
+    // Interior NULs cannot be represented; drop them.
+    let message = message
+        .map(|message| CString::new(message.replace('\0', "")).expect("NUL bytes were removed"));
+    LAST_ERROR.with(|last| *last.borrow_mut() = message);
+}
+
+/// Reads a required string argument.
+///
+/// # Safety
+/// `value` must be null or point to a NUL-terminated string.
+unsafe fn argument<'a>(value: *const c_char, name: &str) -> Result<&'a CStr, Failure> {
+    if value.is_null() {
+        return Err(invalid(format!("{} must not be null", name)));
+    }
+    Ok(CStr::from_ptr(value))
+}
+
+/// Reads a path argument.
+///
+/// # Safety
+/// As for `argument`.
+unsafe fn path(value: *const c_char, name: &str) -> Result<PathBuf, Failure> {
+    let value = argument(value, name)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        Ok(PathBuf::from(std::ffi::OsStr::from_bytes(value.to_bytes())))
+    }
+    #[cfg(not(unix))]
+    {
+        value
+            .to_str()
+            .map(PathBuf::from)
+            .map_err(|_| invalid(format!("{} is not valid UTF-8", name)))
+    }
+}
+
+/// Reads an optional format argument; null means PDF.
+///
+/// # Safety
+/// As for `argument`.
+unsafe fn format(value: *const c_char) -> Result<OutputFormat, Failure> {
+    if value.is_null() {
+        return Ok(OutputFormat::Pdf);
+    }
+    CStr::from_ptr(value)
+        .to_str()
+        .map_err(|_| invalid("format is not valid UTF-8"))?
+        .parse()
+        .map_err(|error: crate::UnknownFormat| invalid(error.to_string()))
+}
+
+/// Converts `input_path` into `format` (an extension such as `"pdf"` or
+/// `"odt"`, or null for PDF), writing `output_path`, with the default
+/// settings.
+///
+/// # Safety
+/// `input_path` and `output_path` must point to NUL-terminated strings;
+/// `format` must be null or do so.
+#[no_mangle]
+pub unsafe extern "C" fn ditto_convert(
+    input_path: *const c_char,
+    output_path: *const c_char,
+    format: *const c_char,
+) -> DittoStatus {
+    convert_with(&Converter::new(), input_path, output_path, format)
+}
+
+/// Converts a `.docx` file to `.pdf` with the default settings.
+///
+/// # Safety
+/// `input_path` and `output_path` must point to NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn ditto_docx_to_pdf(
+    input_path: *const c_char,
+    output_path: *const c_char,
+) -> DittoStatus {
+    ditto_convert(input_path, output_path, ptr::null())
+}
+
+/// Creates converter settings with the defaults of `ditto_convert`.
+#[no_mangle]
+pub extern "C" fn ditto_converter_new() -> *mut DittoConverter {
+    Box::into_raw(Box::new(DittoConverter {
+        converter: Converter::new(),
+    }))
+}
+
+/// Releases converter settings. Null is ignored.
+///
+/// # Safety
+/// `converter` must be null or come from `ditto_converter_new`, and must
+/// not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn ditto_converter_free(converter: *mut DittoConverter) {
+    if !converter.is_null() {
+        drop(Box::from_raw(converter));
+    }
+}
+
+/// Kills LibreOffice if a conversion takes longer than `timeout_ms`
+/// milliseconds; 0 removes the limit.
+///
+/// # Safety
+/// `converter` must come from `ditto_converter_new`.
+#[no_mangle]
+pub unsafe extern "C" fn ditto_converter_set_timeout_ms(
+    converter: *mut DittoConverter,
+    timeout_ms: u64,
+) -> DittoStatus {
+    update(converter, |mut converter| {
+        converter.timeout = Some(Duration::from_millis(timeout_ms)).filter(|t| !t.is_zero());
+        Ok(converter)
+    })
+}
+
+/// Runs the LibreOffice executable at `program` rather than looking for
+/// one.
+///
+/// # Safety
+/// `converter` must come from `ditto_converter_new`; `program` must point
+/// to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn ditto_converter_set_program(
+    converter: *mut DittoConverter,
+    program: *const c_char,
+) -> DittoStatus {
+    update(converter, |converter| {
+        Ok(converter.program(path(program, "program")?))
+    })
+}
+
+/// Whether an existing output file is replaced (the default) or the
+/// conversion fails with `DITTO_STATUS_OUTPUT_ERROR`.
+///
+/// # Safety
+/// `converter` must come from `ditto_converter_new`.
+#[no_mangle]
+pub unsafe extern "C" fn ditto_converter_set_overwrite(
+    converter: *mut DittoConverter,
+    overwrite: bool,
+) -> DittoStatus {
+    update(converter, |converter| Ok(converter.overwrite(overwrite)))
+}
+
+/// Like `ditto_convert`, with the given settings.
+///
+/// # Safety
+/// `converter` must come from `ditto_converter_new`; the strings as for
+/// `ditto_convert`.
+#[no_mangle]
+pub unsafe extern "C" fn ditto_converter_convert(
+    converter: *const DittoConverter,
+    input_path: *const c_char,
+    output_path: *const c_char,
+    format: *const c_char,
+) -> DittoStatus {
+    match converter.as_ref() {
+        Some(converter) => convert_with(&converter.converter, input_path, output_path, format),
+        None => record(|| Err(invalid("converter must not be null"))),
+    }
+}
+
+/// Returns the message of the last call on this thread that failed, or null
+/// if it succeeded. Release the message with `ditto_string_free`.
+#[no_mangle]
+pub extern "C" fn ditto_last_error_message() -> *mut c_char {
+    LAST_ERROR.with(|last| match &*last.borrow() {
+        Some(message) => message.clone().into_raw(),
+        None => ptr::null_mut(),
+    })
+}
+
+/// Releases a string returned by ditto. Null is ignored.
+///
+/// # Safety
+/// `string` must be null or come from a ditto function, and must not be
+/// used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn ditto_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+/// # Safety
+/// As for `ditto_convert`.
+unsafe fn convert_with(
+    converter: &Converter,
+    input_path: *const c_char,
+    output_path: *const c_char,
+    format_name: *const c_char,
+) -> DittoStatus {
+    record(|| {
+        let input_path = path(input_path, "input_path")?;
+        let output_path = path(output_path, "output_path")?;
+        let format = format(format_name)?;
+        converter.convert(&input_path, &output_path, format)?;
+        Ok(())
+    })
+}
+
+/// Replaces the converter's settings with the result of `change`.
+///
+/// # Safety
+/// `converter` must be null or come from `ditto_converter_new`.
+unsafe fn update(
+    converter: *mut DittoConverter,
+    change: impl FnOnce(Converter) -> Result<Converter, Failure>,
+) -> DittoStatus {
+    record(|| {
+        let converter = converter
+            .as_mut()
+            .ok_or_else(|| invalid("converter must not be null"))?;
+        converter.converter = change(converter.converter.clone())?;
+        Ok(())
+    })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::test_support::{fake_soffice, write_input, CONVERTING_SCRIPT};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_committed_header_is_up_to_date() {
+        let generated = include_str!(concat!(env!("OUT_DIR"), "/ditto.h"));
+        assert!(
+            include_str!("../include/ditto.h") == generated,
+            "include/ditto.h is out of date; copy {}/ditto.h over it",
+            env!("OUT_DIR")
+        );
+    }
+
+    fn c_path(path: &std::path::Path) -> CString {
+        use std::os::unix::ffi::OsStrExt;
+        CString::new(path.as_os_str().as_bytes()).unwrap()
+    }
+
+    fn last_error() -> Option<String> {
+        let message = ditto_last_error_message();
+        if message.is_null() {
+            return None;
+        }
+        let text = unsafe { CStr::from_ptr(message) }
+            .to_string_lossy()
+            .into_owned();
+        unsafe { ditto_string_free(message) };
+        Some(text)
+    }
+
+    #[test]
+    fn test_converter_convert_with_settings() {
+        let temp_dir = tempdir().unwrap();
+        let soffice = fake_soffice(temp_dir.path(), CONVERTING_SCRIPT);
+        let input = write_input(temp_dir.path(), "report.docx");
+        let output = temp_dir.path().join("report.odt");
+        std::fs::write(&output, "keep").unwrap();
+
+        let converter = ditto_converter_new();
+        unsafe {
+            assert_eq!(
+                ditto_converter_set_program(converter, c_path(&soffice).as_ptr()),
+                DittoStatus::Ok
+            );
+            assert_eq!(
+                ditto_converter_set_overwrite(converter, false),
+                DittoStatus::Ok
+            );
+            let status = ditto_converter_convert(
+                converter,
+                c_path(&input).as_ptr(),
+                c_path(&output).as_ptr(),
+                c"odt".as_ptr(),
+            );
+            assert_eq!(status, DittoStatus::OutputError);
+            assert!(last_error().unwrap().contains("exists"));
+
+            assert_eq!(
+                ditto_converter_set_overwrite(converter, true),
+                DittoStatus::Ok
+            );
+            assert_eq!(last_error(), None);
+            let status = ditto_converter_convert(
+                converter,
+                c_path(&input).as_ptr(),
+                c_path(&output).as_ptr(),
+                c"odt".as_ptr(),
+            );
+            assert_eq!(status, DittoStatus::Ok);
+            ditto_converter_free(converter);
+        }
+        assert_eq!(
+            std::fs::read_to_string(&output).unwrap(),
+            "%PDF-1.4 converted from report.docx\n"
+        );
+    }
+
+    #[test]
+    fn test_failures_set_status_and_message() {
+        let temp_dir = tempdir().unwrap();
+        let missing = c_path(&temp_dir.path().join("missing.docx"));
+        let output = c_path(&temp_dir.path().join("out.pdf"));
+
+        unsafe {
+            let status = ditto_docx_to_pdf(missing.as_ptr(), output.as_ptr());
+            assert_eq!(status, DittoStatus::InputError);
+            assert!(last_error().unwrap().contains("missing.docx"));
+
+            let status = ditto_convert(missing.as_ptr(), ptr::null(), ptr::null());
+            assert_eq!(status, DittoStatus::InvalidArgument);
+            assert_eq!(
+                last_error().as_deref(),
+                Some("output_path must not be null")
+            );
+
+            let status = ditto_convert(missing.as_ptr(), output.as_ptr(), c"exe".as_ptr());
+            assert_eq!(status, DittoStatus::InvalidArgument);
+
+            let status = ditto_converter_set_timeout_ms(ptr::null_mut(), 1000);
+            assert_eq!(status, DittoStatus::InvalidArgument);
+        }
+    }
+}
@@ -0,0 +1,283 @@
+//! Output formats LibreOffice can convert documents into.
+
+use crate::sniff::DocumentFamily;
+use std::fmt;
+use std::str::FromStr;
+
+/// A target format for [`convert`](crate::convert).
+///
+/// Which formats make sense depends on the input: text documents convert to
+/// the word-processing formats, spreadsheets to the spreadsheet formats and
+/// presentations to the presentation formats. PDF, HTML and the image
+/// formats work for all of them (images contain the first page only).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OutputFormat {
+    /// Portable Document Format.
+    Pdf,
+    /// Office Open XML text document (Word 2007+).
+    Docx,
+    /// Legacy Word 97-2003 document.
+    Doc,
+    /// OpenDocument text.
+    Odt,
+    /// Rich Text Format.
+    Rtf,
+    /// HTML page.
+    Html,
+    /// Plain text, UTF-8 encoded.
+    Txt,
+    /// EPUB e-book.
+    Epub,
+    /// Office Open XML spreadsheet (Excel 2007+).
+    Xlsx,
+    /// OpenDocument spreadsheet.
+    Ods,
+    /// Comma-separated values, UTF-8 encoded (first sheet only).
+    Csv,
+    /// Office Open XML presentation (PowerPoint 2007+).
+    Pptx,
+    /// OpenDocument presentation.
+    Odp,
+    /// PNG image of the first page.
+    Png,
+    /// JPEG image of the first page.
+    Jpg,
+    /// SVG image of the first page.
+    Svg,
+}
+
+impl OutputFormat {
+    /// Every supported format.
+    pub const ALL: [OutputFormat; 16] = [
+        OutputFormat::Pdf,
+        OutputFormat::Docx,
+        OutputFormat::Doc,
+        OutputFormat::Odt,
+        OutputFormat::Rtf,
+        OutputFormat::Html,
+        OutputFormat::Txt,
+        OutputFormat::Epub,
+        OutputFormat::Xlsx,
+        OutputFormat::Ods,
+        OutputFormat::Csv,
+        OutputFormat::Pptx,
+        OutputFormat::Odp,
+        OutputFormat::Png,
+        OutputFormat::Jpg,
+        OutputFormat::Svg,
+    ];
+
+    /// The file extension LibreOffice gives converted files, without the dot.
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Pdf => "pdf",
+            OutputFormat::Docx => "docx",
+            OutputFormat::Doc => "doc",
+            OutputFormat::Odt => "odt",
+            OutputFormat::Rtf => "rtf",
+            OutputFormat::Html => "html",
+            OutputFormat::Txt => "txt",
+            OutputFormat::Epub => "epub",
+            OutputFormat::Xlsx => "xlsx",
+            OutputFormat::Ods => "ods",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Pptx => "pptx",
+            OutputFormat::Odp => "odp",
+            OutputFormat::Png => "png",
+            OutputFormat::Jpg => "jpg",
+            OutputFormat::Svg => "svg",
+        }
+    }
+
+    /// The MIME type of files in this format.
+    pub fn media_type(self) -> &'static str {
+        match self {
+            OutputFormat::Pdf => "application/pdf",
+            OutputFormat::Docx => {
+                "application/vnd.openxmlformats-officedocument.wordprocessingml.document"
+            }
+            OutputFormat::Doc => "application/msword",
+            OutputFormat::Odt => "application/vnd.oasis.opendocument.text",
+            OutputFormat::Rtf => "application/rtf",
+            OutputFormat::Html => "text/html; charset=utf-8",
+            OutputFormat::Txt => "text/plain; charset=utf-8",
+            OutputFormat::Epub => "application/epub+zip",
+            OutputFormat::Xlsx => {
+                "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"
+            }
+            OutputFormat::Ods => "application/vnd.oasis.opendocument.spreadsheet",
+            OutputFormat::Csv => "text/csv; charset=utf-8",
+            OutputFormat::Pptx => {
+                "application/vnd.openxmlformats-officedocument.presentationml.presentation"
+            }
+            OutputFormat::Odp => "application/vnd.oasis.opendocument.presentation",
+            OutputFormat::Png => "image/png",
+            OutputFormat::Jpg => "image/jpeg",
+            OutputFormat::Svg => "image/svg+xml",
+        }
+    }
+
+    /// The LibreOffice export filter (and its options) to force, if the
+    /// default LibreOffice picks for the extension is not the one we want.
+    ///
+    /// Formats that are ambiguous (`docx` could be "Office Open XML Text" or
+    /// "MS Word 2007 XML") or that take options (text encodings) are pinned
+    /// here; for the rest LibreOffice chooses based on the document type.
+    pub fn filter(self) -> Option<&'static str> {
+        match self {
+            OutputFormat::Docx => Some("MS Word 2007 XML"),
+            OutputFormat::Doc => Some("MS Word 97"),
+            OutputFormat::Txt => Some("Text (encoded):UTF8"),
+            OutputFormat::Xlsx => Some("Calc MS Excel 2007 XML"),
+            // Comma separated, double-quoted text, UTF-8 (charset 76).
+            OutputFormat::Csv => Some("Text - txt - csv (StarCalc):44,34,76"),
+            OutputFormat::Pptx => Some("Impress MS PowerPoint 2007 XML"),
+            _ => None,
+        }
+    }
+
+    /// The export filter name and filter options LibreOffice's `storeToURL`
+    /// needs to write a document of `family` in this format.
+    ///
+    /// Unlike `--convert-to`, storing over UNO does not pick a filter from
+    /// the extension, so every combination has to be spelled out.
+    pub(crate) fn export_filter(
+        self,
+        family: DocumentFamily,
+    ) -> (&'static str, Option<&'static str>) {
+        use DocumentFamily::*;
+        let by_family = |text, spreadsheet, presentation, drawing| match family {
+            Text => text,
+            Spreadsheet => spreadsheet,
+            Presentation => presentation,
+            Drawing => drawing,
+        };
+        match self {
+            OutputFormat::Pdf => (
+                by_family(
+                    "writer_pdf_Export",
+                    "calc_pdf_Export",
+                    "impress_pdf_Export",
+                    "draw_pdf_Export",
+                ),
+                None,
+            ),
+            OutputFormat::Docx => ("MS Word 2007 XML", None),
+            OutputFormat::Doc => ("MS Word 97", None),
+            OutputFormat::Odt => ("writer8", None),
+            OutputFormat::Rtf => ("Rich Text Format", None),
+            OutputFormat::Html => (
+                by_family(
+                    "HTML (StarWriter)",
+                    "HTML (StarCalc)",
+                    "impress_html_Export",
+                    "draw_html_Export",
+                ),
+                None,
+            ),
+            OutputFormat::Txt => ("Text (encoded)", Some("UTF8")),
+            OutputFormat::Epub => ("EPUB", None),
+            OutputFormat::Xlsx => ("Calc MS Excel 2007 XML", None),
+            OutputFormat::Ods => ("calc8", None),
+            OutputFormat::Csv => ("Text - txt - csv (StarCalc)", Some("44,34,76")),
+            OutputFormat::Pptx => ("Impress MS PowerPoint 2007 XML", None),
+            OutputFormat::Odp => ("impress8", None),
+            OutputFormat::Png => (
+                by_family(
+                    "writer_png_Export",
+                    "calc_png_Export",
+                    "impress_png_Export",
+                    "draw_png_Export",
+                ),
+                None,
+            ),
+            OutputFormat::Jpg => (
+                by_family(
+                    "writer_jpg_Export",
+                    "calc_jpg_Export",
+                    "impress_jpg_Export",
+                    "draw_jpg_Export",
+                ),
+                None,
+            ),
+            OutputFormat::Svg => (
+                by_family(
+                    "writer_svg_Export",
+                    "calc_svg_Export",
+                    "impress_svg_Export",
+                    "draw_svg_Export",
+                ),
+                None,
+            ),
+        }
+    }
+
+    /// The value passed to `soffice --convert-to`.
+    pub(crate) fn convert_to_arg(self) -> String {
+        match self.filter() {
+            Some(filter) => format!("{}:{}", self.extension(), filter),
+            None => self.extension().to_string(),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.extension())
+    }
+}
+
+/// Error returned when parsing an unknown [`OutputFormat`] name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownFormat(pub String);
+
+impl fmt::Display for UnknownFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown output format '{}'", self.0)
+    }
+}
+
+impl std::error::Error for UnknownFormat {}
+
+impl FromStr for OutputFormat {
+    type Err = UnknownFormat;
+
+    /// Parses a format from its extension, case-insensitively (`"PDF"`,
+    /// `"docx"`, `"jpeg"`, ...).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().trim_start_matches('.').to_ascii_lowercase();
+        match name.as_str() {
+            "jpeg" => Ok(OutputFormat::Jpg),
+            "htm" => Ok(OutputFormat::Html),
+            "text" => Ok(OutputFormat::Txt),
+            _ => OutputFormat::ALL
+                .into_iter()
+                .find(|format| format.extension() == name)
+                .ok_or_else(|| UnknownFormat(s.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_round_trips_every_format() {
+        for format in OutputFormat::ALL {
+            assert_eq!(format.to_string().parse::<OutputFormat>(), Ok(format));
+        }
+        assert_eq!(".JPEG".parse::<OutputFormat>(), Ok(OutputFormat::Jpg));
+        assert!("exe".parse::<OutputFormat>().is_err());
+    }
+
+    #[test]
+    fn test_convert_to_arg_includes_filter() {
+        assert_eq!(OutputFormat::Pdf.convert_to_arg(), "pdf");
+        assert_eq!(OutputFormat::Docx.convert_to_arg(), "docx:MS Word 2007 XML");
+        assert_eq!(
+            OutputFormat::Txt.convert_to_arg(),
+            "txt:Text (encoded):UTF8"
+        );
+    }
+}
@@ -1,12 +1,34 @@
 //! # Document Conversion Library
 //!
 //! This library provides utilities for converting `.docx` files to `.pdf` using LibreOffice (`soffice`).
+//! Other formats LibreOffice understands are supported through [`convert`] and [`OutputFormat`].
 //! It ensures proper validation of input and output paths before invoking the conversion process.
 //!
 //! ## Requirements
-//! - LibreOffice (`soffice`) must be installed and available in the system's `PATH`.
+//! - LibreOffice (`soffice`) must be installed, either in a well-known location
+//!   (such as `/opt/libreoffice*/program`), in the system's `PATH`, or at the path
+//!   given by the `DITTO_SOFFICE` environment variable.
 //! - The function assumes that the output directory exists or can be created.
 //!
+//! ## Concurrency
+//! Every conversion runs LibreOffice with its own temporary user profile, so
+//! conversions can be started from several threads at once. See [`ProfileMode`]
+//! to reuse a profile directory instead.
+//!
+//! Starting LibreOffice takes seconds. To avoid paying that on every call,
+//! [`convert_batch`] converts many files in one run, and a [`WorkerPool`]
+//! (see [`Converter::start_pool`]) keeps LibreOffice processes running
+//! between conversions.
+//!
+//! Each LibreOffice needs hundreds of megabytes of memory. A
+//! [`ConversionQueue`] (see [`Converter::start_queue`]) runs submitted
+//! conversions at most a given number at a time, by priority.
+//!
+//! ## Retries
+//! LibreOffice sometimes fails for no reason of the document's, most often
+//! on a cold start. A [`RetryPolicy`] (see [`Converter::retry`]) runs such
+//! conversions again after a backoff, and reports how many attempts it took.
+//!
 //! ## Example Usage
 //! ```no_run
 //! use std::path::Path;
@@ -21,28 +43,179 @@
 //! }
 //! ```
 
+#[cfg(feature = "async")]
+pub mod asynchronous;
+mod converter;
+mod error;
+#[cfg(feature = "ffi")]
+mod ffi;
+mod format;
+mod locate;
+mod memory;
+mod pdf;
+mod persist;
+mod pool;
+mod process;
+mod profile;
+#[cfg(feature = "python")]
+mod python;
+mod queue;
+mod report;
+mod retry;
+mod secret;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod sniff;
+#[cfg(test)]
+mod test_support;
+mod uno;
+
+pub use converter::Converter;
+pub use error::{DittoError, ErrorKind, Result};
+pub use format::{OutputFormat, UnknownFormat};
+pub use locate::{PYTHON_ENV, SOFFICE_ENV};
+pub use pdf::{ChangePermission, PdfA, PdfExportOptions, PdfPermissions, PrintPermission};
+pub use pool::{PoolOptions, WorkerPool};
+pub use profile::ProfileMode;
+pub use queue::{ConversionQueue, JobHandle};
+pub use report::{BatchResult, ConversionReport, StreamReport};
+pub use retry::RetryPolicy;
+pub use sniff::{DocumentFamily, InputFormat};
+
+use std::io::{Read, Write};
 use std::path::Path;
-use std::process::Command;
+
+/// Converts a document into `format` using LibreOffice (`soffice`).
+///
+/// The input can be anything LibreOffice opens: text documents (DOCX, DOC,
+/// ODT, RTF, ...), spreadsheets (XLSX, XLS, ODS, CSV) or presentations (PPTX,
+/// PPT, ODP). The output is written to `output_path`, whatever its extension.
+///
+/// # Errors
+/// This function returns an error if:
+/// - The input file does not exist ([`DittoError::InputNotFound`]) or is not a
+///   regular file ([`DittoError::InputNotAFile`]).
+/// - The output path points to an existing directory ([`DittoError::OutputIsDirectory`])
+///   or to the input itself ([`DittoError::OutputIsInput`]).
+/// - The output file exists and overwriting was turned off ([`DittoError::OutputExists`]);
+///   see [`Converter::overwrite`].
+/// - The input's contents do not match its extension ([`DittoError::UnrecognizedInput`])
+///   or cannot be exported to `format` ([`DittoError::UnsupportedConversion`]).
+///   Both are checked before LibreOffice is launched; see [`Converter::check_input`].
+/// - The input is password protected ([`DittoError::EncryptedInput`]); see
+///   [`Converter::input_password`].
+/// - LibreOffice cannot be started ([`DittoError::ConverterNotFound`]) or fails to
+///   convert the file ([`DittoError::ConverterFailed`]).
+/// - The converted file is not found after conversion ([`DittoError::OutputMissing`]).
+///
+/// # Example
+/// ```no_run
+/// use std::path::Path;
+/// use ditto::{convert, OutputFormat};
+///
+/// convert(Path::new("budget.xlsx"), Path::new("budget.ods"), OutputFormat::Ods)?;
+/// # Ok::<(), ditto::DittoError>(())
+/// ```
+pub fn convert(
+    input_path: &Path,
+    output_path: &Path,
+    format: OutputFormat,
+) -> Result<ConversionReport> {
+    Converter::new().convert(input_path, output_path, format)
+}
+
+/// Converts a document held in memory into `format`, returning the
+/// converted bytes.
+///
+/// `extension` names the input's format the way a file extension would
+/// (`"docx"`, `"csv"`, ...). The input is converted in a temporary directory
+/// that is removed afterwards, even on error. See [`Converter::convert_bytes`].
+///
+/// # Errors
+/// Same as [`convert`], plus [`DittoError::InvalidOptions`] for a malformed
+/// `extension`.
+pub fn convert_bytes(input: &[u8], extension: &str, format: OutputFormat) -> Result<Vec<u8>> {
+    Converter::new().convert_bytes(input, extension, format)
+}
+
+/// Converts a document read from `reader` into `format`, writing the result
+/// to `writer`.
+///
+/// `extension` names the input's format as for [`convert_bytes`]. Use
+/// [`Converter::convert_stream`] with [`Converter::max_input_size`] to bound
+/// how much is read.
+///
+/// # Errors
+/// Same as [`convert_bytes`]; failing to read or write is [`DittoError::Io`].
+pub fn convert_stream<R: Read, W: Write>(
+    reader: R,
+    writer: W,
+    extension: &str,
+    format: OutputFormat,
+) -> Result<StreamReport> {
+    Converter::new().convert_stream(reader, writer, extension, format)
+}
+
+/// Converts many documents into `format` in as few LibreOffice runs as
+/// possible, writing `<output_dir>/<input stem>.<extension>` for each input.
+///
+/// Returns one [`BatchResult`] per input, in order; a document that fails
+/// to convert does not fail the others. See [`Converter::convert_batch`].
+///
+/// # Errors
+/// The whole batch fails only if `output_dir` cannot be created or
+/// LibreOffice cannot be started ([`DittoError::ConverterNotFound`]).
+///
+/// # Example
+/// ```no_run
+/// use std::path::Path;
+/// use ditto::{convert_batch, OutputFormat};
+///
+/// let inputs = ["a.docx", "b.docx", "c.odt"];
+/// for item in convert_batch(&inputs, Path::new("pdf"), OutputFormat::Pdf)? {
+///     if let Err(error) = item.result {
+///         eprintln!("{}: {}", item.input_path.display(), error);
+///     }
+/// }
+/// # Ok::<(), ditto::DittoError>(())
+/// ```
+pub fn convert_batch<P: AsRef<Path>>(
+    inputs: &[P],
+    output_dir: &Path,
+    format: OutputFormat,
+) -> Result<Vec<BatchResult>> {
+    Converter::new().convert_batch(inputs, output_dir, format)
+}
 
 /// Converts a `.docx` file to `.pdf` using LibreOffice (`soffice`).
 ///
+/// This is [`convert`] with [`OutputFormat::Pdf`].
+///
 /// # Arguments
 /// * `input_path` - The path to the `.docx` file that needs to be converted.
 /// * `output_path` - The desired output path for the `.pdf` file.
 ///
 /// # Returns
-/// * `Ok(())` if the conversion is successful.
-/// * `Err(Box<dyn std::error::Error>)` if an error occurs during validation or conversion.
+/// * `Ok(ConversionReport)` if the conversion is successful, including anything
+///   LibreOffice printed while converting.
+/// * `Err(DittoError)` if an error occurs during validation or conversion.
 ///
 /// # Errors
 /// This function returns an error if:
-/// - The input file does not exist or is not a valid file.
-/// - The output path points to an existing directory.
-/// - LibreOffice fails to convert the file.
-/// - The expected output PDF file is not found after conversion.
+/// - The input file does not exist ([`DittoError::InputNotFound`]) or is not a
+///   regular file ([`DittoError::InputNotAFile`]).
+/// - The output path points to an existing directory ([`DittoError::OutputIsDirectory`])
+///   or to the input itself ([`DittoError::OutputIsInput`]).
+/// - The input is not actually a DOCX document ([`DittoError::UnrecognizedInput`]).
+/// - LibreOffice cannot be started ([`DittoError::ConverterNotFound`]) or fails to
+///   convert the file ([`DittoError::ConverterFailed`]).
+/// - The expected output PDF file is not found after conversion ([`DittoError::OutputMissing`]).
 ///
 /// # Requirements
-/// - LibreOffice (`soffice`) must be installed and accessible via the system `PATH`.
+/// - LibreOffice (`soffice`) must be installed; see [`Converter`] for how it is located.
+///
+/// This function waits for LibreOffice indefinitely. Use [`Converter`] to set a
+/// timeout.
 ///
 /// # Example
 /// ```no_run
@@ -56,66 +229,8 @@ use std::process::Command;
 ///     eprintln!("Conversion failed: {}", e);
 /// }
 /// ```
-pub fn docx_to_pdf(
-    input_path: &Path,
-    output_path: &Path
-) -> Result<(), Box<dyn std::error::Error>> {
-    if !input_path.exists() {
-        return Err(
-            format!("Input file not found: {}", input_path.display()).into()
-        );
-    }
-    if !input_path.is_file() {
-        return Err(
-            format!("Input path is not a file: {}", input_path.display()).into()
-        );
-    }
-
-    if output_path.exists() && output_path.is_dir() {
-        return Err("Output path is a directory".into());
-    }
-
-    let output_dir = output_path
-        .parent()
-        .ok_or("Output path has no parent directory")?;
-    std::fs::create_dir_all(output_dir)?;
-
-    let status = Command::new("soffice")
-        .args(&[
-            "--headless",
-            "--convert-to",
-            "pdf",
-            "--outdir",
-            output_dir.to_str().ok_or("Invalid output directory")?,
-            input_path.to_str().ok_or("Invalid input path")?,
-        ])
-        .status()?;
-
-    if !status.success() {
-        return Err(format!(
-            "Conversion failed with exit code: {:?}",
-            status.code()
-        )
-        .into());
-    }
-
-    let generated_pdf = output_dir
-        .join(input_path.file_stem().ok_or("Input file has no stem")?)
-        .with_extension("pdf");
-
-    if !generated_pdf.exists() {
-        return Err(format!(
-            "Generated PDF not found at: {}",
-            generated_pdf.display()
-        )
-        .into());
-    }
-
-    if generated_pdf != output_path {
-        std::fs::rename(&generated_pdf, output_path)?;
-    }
-
-    Ok(())
+pub fn docx_to_pdf(input_path: &Path, output_path: &Path) -> Result<ConversionReport> {
+    Converter::new().docx_to_pdf(input_path, output_path)
 }
 
 #[cfg(test)]
@@ -131,7 +246,15 @@ mod tests {
         let input_path = temp_dir.path().join("nonexistent.docx");
         let output_path = temp_dir.path().join("output.pdf");
         let result = docx_to_pdf(&input_path, &output_path);
-        assert!(result.is_err());
+        assert!(matches!(result, Err(DittoError::InputNotFound(_))));
+    }
+
+    #[test]
+    fn test_docx_to_pdf_input_is_directory() {
+        let temp_dir = tempdir().unwrap();
+        let output_path = temp_dir.path().join("output.pdf");
+        let result = docx_to_pdf(temp_dir.path(), &output_path);
+        assert!(matches!(result, Err(DittoError::InputNotAFile(_))));
     }
 
     #[test]
@@ -143,6 +266,20 @@ mod tests {
         let output_path = temp_dir.path().join("some_dir");
         std::fs::create_dir(&output_path).unwrap();
         let result = docx_to_pdf(&input_path, &output_path);
-        assert!(result.is_err());
+        assert!(matches!(result, Err(DittoError::OutputIsDirectory(_))));
+    }
+
+    #[test]
+    fn test_convert_output_is_input() {
+        let temp_dir = tempdir().unwrap();
+        let input_path = temp_dir.path().join("report.docx");
+        std::fs::write(&input_path, "Test content").unwrap();
+        let output_path = temp_dir.path().join(".").join("report.docx");
+        let result = Converter::new()
+            .program(temp_dir.path().join("soffice"))
+            .convert(&input_path, &output_path, OutputFormat::Docx);
+        assert!(matches!(result, Err(DittoError::OutputIsInput(_))));
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::Usage);
+        assert_eq!(std::fs::read(&input_path).unwrap(), b"Test content");
     }
 }
@@ -0,0 +1,157 @@
+//! Finding the LibreOffice executable.
+
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// Environment variable that overrides which `soffice` executable is used.
+pub const SOFFICE_ENV: &str = "DITTO_SOFFICE";
+
+/// Environment variable that overrides which Python runs the UNO helper.
+pub const PYTHON_ENV: &str = "DITTO_PYTHON";
+
+/// Program name looked up in `PATH` when nothing else is found.
+const DEFAULT_PROGRAM: &str = "soffice";
+
+/// Fixed locations where distributions and the official packages install
+/// LibreOffice, checked in order.
+const KNOWN_LOCATIONS: &[&str] = &[
+    "/usr/lib/libreoffice/program/soffice",
+    "/usr/lib64/libreoffice/program/soffice",
+    "/usr/local/lib/libreoffice/program/soffice",
+    "/snap/bin/libreoffice",
+    "/Applications/LibreOffice.app/Contents/MacOS/soffice",
+];
+
+/// Picks the `soffice` executable to run.
+///
+/// In order of preference: the explicitly configured `program`, the
+/// [`SOFFICE_ENV`] environment variable, an installation found in a
+/// well-known location, and finally plain `soffice` resolved through `PATH`.
+pub(crate) fn resolve(program: Option<&Path>) -> PathBuf {
+    resolve_with(program, std::env::var_os(SOFFICE_ENV), &candidates())
+}
+
+/// Picks the Python interpreter that runs the UNO helper script.
+///
+/// In order of preference: the explicitly configured `python`, the
+/// [`PYTHON_ENV`] environment variable, the interpreter bundled next to
+/// `soffice` (official LibreOffice builds), and `python3` from `PATH`
+/// (distribution builds, where `uno` is a system Python module).
+pub(crate) fn resolve_python(python: Option<&Path>, soffice: &Path) -> PathBuf {
+    if let Some(python) = python {
+        return python.to_path_buf();
+    }
+    if let Some(python) = std::env::var_os(PYTHON_ENV).filter(|value| !value.is_empty()) {
+        return PathBuf::from(python);
+    }
+    let bundled = which(soffice)
+        .and_then(|soffice| soffice.canonicalize().ok())
+        .and_then(|soffice| Some(soffice.parent()?.join("python")));
+    match bundled {
+        Some(bundled) if bundled.is_file() => bundled,
+        _ => PathBuf::from("python3"),
+    }
+}
+
+/// Resolves a bare program name through `PATH`.
+pub(crate) fn which(program: &Path) -> Option<PathBuf> {
+    if program.components().count() > 1 {
+        return Some(program.to_path_buf());
+    }
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(program))
+        .find(|candidate| candidate.is_file())
+}
+
+fn resolve_with(
+    program: Option<&Path>,
+    env_override: Option<OsString>,
+    candidates: &[PathBuf],
+) -> PathBuf {
+    if let Some(program) = program {
+        return program.to_path_buf();
+    }
+    if let Some(program) = env_override.filter(|value| !value.is_empty()) {
+        return PathBuf::from(program);
+    }
+    candidates
+        .iter()
+        .find(|candidate| candidate.is_file())
+        .cloned()
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_PROGRAM))
+}
+
+/// Well-known install locations, with the newest `/opt/libreoffice*`
+/// installation (as shipped by the upstream packages) first.
+fn candidates() -> Vec<PathBuf> {
+    let mut opt_installs: Vec<PathBuf> = std::fs::read_dir("/opt")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with("libreoffice")
+        })
+        .map(|entry| entry.path().join("program").join("soffice"))
+        .collect();
+    // "libreoffice24.8" sorts after "libreoffice7.6" only by version, so
+    // compare the numeric parts rather than the raw names.
+    opt_installs.sort_by_key(|path| std::cmp::Reverse(version_key(path)));
+
+    opt_installs
+        .into_iter()
+        .chain(KNOWN_LOCATIONS.iter().map(PathBuf::from))
+        .collect()
+}
+
+/// Extracts the numbers from an `/opt/libreofficeX.Y/...` directory name.
+fn version_key(path: &Path) -> Vec<u32> {
+    let name = path
+        .ancestors()
+        .nth(2)
+        .and_then(Path::file_name)
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    name.trim_start_matches("libreoffice")
+        .split('.')
+        .filter_map(|part| part.parse().ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_resolve_prefers_explicit_then_env_then_candidates() {
+        let temp_dir = tempdir().unwrap();
+        let installed = temp_dir.path().join("soffice");
+        std::fs::write(&installed, "").unwrap();
+        let candidates = [temp_dir.path().join("missing"), installed.clone()];
+
+        let explicit = Path::new("/custom/soffice");
+        assert_eq!(
+            resolve_with(Some(explicit), Some("/env/soffice".into()), &candidates),
+            explicit
+        );
+        assert_eq!(
+            resolve_with(None, Some("/env/soffice".into()), &candidates),
+            Path::new("/env/soffice")
+        );
+        assert_eq!(resolve_with(None, Some("".into()), &candidates), installed);
+        assert_eq!(
+            resolve_with(None, None, &candidates[..1]),
+            Path::new("soffice")
+        );
+    }
+
+    #[test]
+    fn test_version_key_orders_opt_installs() {
+        let old = Path::new("/opt/libreoffice7.6/program/soffice");
+        let new = Path::new("/opt/libreoffice24.8/program/soffice");
+        assert!(version_key(new) > version_key(old));
+    }
+}
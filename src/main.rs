@@ -1,9 +1,568 @@
-use ditto::docx_to_pdf;
-use std::path::Path;
+use ditto::{Converter, DittoError, ErrorKind, OutputFormat, PdfA, PdfExportOptions};
+use std::ffi::{OsStr, OsString};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
 
-fn main() {
-    let input_doc = Path::new("document.docx");
-    let output_path = Path::new("document.pdf");
+const USAGE: &str = "\
+Usage: ditto --input <FILE> [--output <FILE>] [--to <FORMAT>] [--pdfa <LEVEL>]
+             [--pages <RANGE>] [--timeout <SECONDS>] [--soffice <PATH>]
+             [--no-overwrite]
+       ditto serve [--listen <ADDRESS>] [--threads <N>] [--timeout <SECONDS>]
+                   [--soffice <PATH>] [--max-upload-size <BYTES>]
 
-    let _ = docx_to_pdf(input_doc, output_path);
+Converts a document (DOCX to PDF by default) using LibreOffice (soffice), or
+runs an HTTP conversion service.
+
+Options:
+  -i, --input <FILE>    Path to the document to convert
+  -o, --output <FILE>   Path of the file to write [default: input with the
+                        extension of the target format]
+  -f, --to <FORMAT>     Target format: pdf, docx, doc, odt, rtf, html, txt, epub,
+                        xlsx, ods, csv, pptx, odp, png, jpg, svg [default: pdf]
+      --pdfa <LEVEL>    Produce PDF/A-1b, PDF/A-2b or PDF/A-3b (LEVEL is 1, 2 or 3)
+      --pages <RANGE>   Export only these pages to PDF, e.g. 1-3,5
+  -t, --timeout <SECONDS>
+                        Kill LibreOffice if the conversion takes longer than this
+      --soffice <PATH>  LibreOffice executable to run [default: $DITTO_SOFFICE,
+                        a well-known install location, or soffice from PATH]
+      --no-overwrite    Fail instead of replacing an existing output file
+  -h, --help            Print this help and exit
+  -V, --version         Print version information and exit
+
+Serve options:
+  -l, --listen <ADDRESS>
+                        Address to listen on [default: 127.0.0.1:3000]
+      --threads <N>     Conversions to run at once [default: number of CPUs]
+      --max-upload-size <BYTES>
+                        Reject larger uploads [default: 104857600]
+  -t, --timeout, --soffice
+                        As above, for every conversion
+
+  The service answers POST /convert (a multipart/form-data upload with the
+  document as a file and optional format, pdfa and pages fields) with the
+  converted document, and GET /health and GET /ready with 200 when
+  LibreOffice can be found and started.
+
+Exit codes:
+  0  Conversion succeeded
+  1  Conversion failed
+  2  Invalid command-line usage, or the output path is the input file
+  3  Input file is missing, is not a file, is not a supported document, or is
+     password protected
+  4  Output path is invalid or the output exists with --no-overwrite
+  5  LibreOffice (soffice) could not be started
+  6  Conversion timed out
+";
+
+/// Process exit codes, one per failure class, so shell callers can tell
+/// a bad input apart from a converter failure.
+const EXIT_CONVERSION_FAILED: u8 = 1;
+const EXIT_USAGE: u8 = 2;
+const EXIT_INPUT: u8 = 3;
+const EXIT_OUTPUT: u8 = 4;
+const EXIT_CONVERTER_UNAVAILABLE: u8 = 5;
+const EXIT_TIMEOUT: u8 = 6;
+
+/// What the command line asked us to do.
+#[derive(Debug, PartialEq)]
+enum Command {
+    Help,
+    Version,
+    Convert {
+        input: PathBuf,
+        output: PathBuf,
+        format: OutputFormat,
+        pdf_options: PdfExportOptions,
+        timeout: Option<Duration>,
+        soffice: Option<PathBuf>,
+        overwrite: bool,
+    },
+    Serve {
+        listen: String,
+        threads: Option<usize>,
+        timeout: Option<Duration>,
+        soffice: Option<PathBuf>,
+        max_upload_size: Option<u64>,
+    },
+}
+
+/// Address `ditto serve` listens on unless told otherwise.
+const DEFAULT_LISTEN: &str = "127.0.0.1:3000";
+
+/// Long options that take a value, and so may be written as `--flag=value`.
+const VALUE_FLAGS: &[&str] = &[
+    "--input",
+    "--output",
+    "--to",
+    "--pdfa",
+    "--pages",
+    "--timeout",
+    "--soffice",
+];
+
+/// Like [`VALUE_FLAGS`], for `ditto serve`.
+const SERVE_VALUE_FLAGS: &[&str] = &[
+    "--listen",
+    "--threads",
+    "--timeout",
+    "--soffice",
+    "--max-upload-size",
+];
+
+/// Parses the arguments following the program name.
+fn parse_args<I>(args: I) -> Result<Command, String>
+where
+    I: IntoIterator<Item = OsString>,
+{
+    let mut input: Option<OsString> = None;
+    let mut output: Option<OsString> = None;
+    let mut format: Option<OsString> = None;
+    let mut pdfa: Option<OsString> = None;
+    let mut pages: Option<OsString> = None;
+    let mut timeout: Option<OsString> = None;
+    let mut soffice: Option<OsString> = None;
+    let mut overwrite = true;
+    let mut args = args.into_iter().peekable();
+    if args.peek().is_some_and(|arg| arg == "serve") {
+        args.next();
+        return parse_serve_args(args);
+    }
+
+    while let Some(arg) = args.next() {
+        let (flag, inline_value) = split_flag(&arg, VALUE_FLAGS);
+        let slot = match flag.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-V" | "--version" => return Ok(Command::Version),
+            "--no-overwrite" => {
+                overwrite = false;
+                continue;
+            }
+            "-i" | "--input" => &mut input,
+            "-o" | "--output" => &mut output,
+            "-f" | "--to" => &mut format,
+            "--pdfa" => &mut pdfa,
+            "--pages" => &mut pages,
+            "-t" | "--timeout" => &mut timeout,
+            "--soffice" => &mut soffice,
+            _ => return Err(format!("unexpected argument '{}'", arg.to_string_lossy())),
+        };
+        set_value(slot, &flag, inline_value.or_else(|| args.next()))?;
+    }
+
+    let input = PathBuf::from(input.ok_or("the '--input <FILE>' argument is required")?);
+    let format = match format {
+        Some(value) => value
+            .to_string_lossy()
+            .parse::<OutputFormat>()
+            .map_err(|e| e.to_string())?,
+        None => OutputFormat::Pdf,
+    };
+    let output = output
+        .map(PathBuf::from)
+        .unwrap_or_else(|| input.with_extension(format.extension()));
+    let mut pdf_options = PdfExportOptions::new();
+    if let Some(value) = pdfa {
+        let level = PdfA::parse(&value.to_string_lossy()).ok_or_else(|| {
+            format!(
+                "invalid value '{}' for '--pdfa': expected 1, 2 or 3",
+                value.to_string_lossy()
+            )
+        })?;
+        pdf_options = pdf_options.pdfa(level);
+    }
+    if let Some(value) = pages {
+        pdf_options = pdf_options.page_range(value.to_string_lossy());
+    }
+    let timeout = timeout.map(|value| parse_timeout(&value)).transpose()?;
+    Ok(Command::Convert {
+        input,
+        output,
+        format,
+        pdf_options,
+        timeout,
+        soffice: soffice.map(PathBuf::from),
+        overwrite,
+    })
+}
+
+/// Parses the arguments following `ditto serve`.
+fn parse_serve_args<I>(args: I) -> Result<Command, String>
+where
+    I: IntoIterator<Item = OsString>,
+{
+    let mut listen: Option<OsString> = None;
+    let mut threads: Option<OsString> = None;
+    let mut timeout: Option<OsString> = None;
+    let mut soffice: Option<OsString> = None;
+    let mut max_upload_size: Option<OsString> = None;
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        let (flag, inline_value) = split_flag(&arg, SERVE_VALUE_FLAGS);
+        let slot = match flag.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-V" | "--version" => return Ok(Command::Version),
+            "-l" | "--listen" => &mut listen,
+            "--threads" => &mut threads,
+            "-t" | "--timeout" => &mut timeout,
+            "--soffice" => &mut soffice,
+            "--max-upload-size" => &mut max_upload_size,
+            _ => return Err(format!("unexpected argument '{}'", arg.to_string_lossy())),
+        };
+        set_value(slot, &flag, inline_value.or_else(|| args.next()))?;
+    }
+
+    Ok(Command::Serve {
+        listen: listen.map_or_else(
+            || DEFAULT_LISTEN.to_string(),
+            |value| value.to_string_lossy().into_owned(),
+        ),
+        threads: threads
+            .map(|value| parse_number(&value, "--threads"))
+            .transpose()?,
+        timeout: timeout.map(|value| parse_timeout(&value)).transpose()?,
+        soffice: soffice.map(PathBuf::from),
+        max_upload_size: max_upload_size
+            .map(|value| parse_number(&value, "--max-upload-size"))
+            .transpose()?,
+    })
+}
+
+/// Splits `--flag=value` for the flags in `value_flags`, keeping the value
+/// as raw bytes so non-UTF-8 paths survive.
+fn split_flag(arg: &OsString, value_flags: &[&str]) -> (String, Option<OsString>) {
+    let text = arg.to_string_lossy();
+    match text.split_once('=') {
+        Some((flag, _)) if value_flags.contains(&flag) => {
+            let value = split_inline_value(arg, flag.len() + 1);
+            (flag.to_string(), Some(value))
+        }
+        _ => (text.to_string(), None),
+    }
+}
+
+/// Stores the value given for `flag`, which must be present and not given
+/// before.
+fn set_value(
+    slot: &mut Option<OsString>,
+    flag: &str,
+    value: Option<OsString>,
+) -> Result<(), String> {
+    let value = match value {
+        Some(value) if !value.is_empty() => value,
+        _ => return Err(format!("a value is required for '{}'", flag)),
+    };
+    if slot.replace(value).is_some() {
+        return Err(format!("'{}' was provided more than once", flag));
+    }
+    Ok(())
+}
+
+/// Parses a positive whole number given for `flag`.
+fn parse_number<T: std::str::FromStr + Default + PartialEq>(
+    value: &OsStr,
+    flag: &str,
+) -> Result<T, String> {
+    value
+        .to_str()
+        .and_then(|text| text.parse::<T>().ok())
+        .filter(|number| *number != T::default())
+        .ok_or_else(|| {
+            format!(
+                "invalid value '{}' for '{}': expected a positive number",
+                value.to_string_lossy(),
+                flag
+            )
+        })
+}
+
+/// Parses a `--timeout` value given in (possibly fractional) seconds.
+fn parse_timeout(value: &OsStr) -> Result<Duration, String> {
+    value
+        .to_str()
+        .and_then(|text| text.parse::<f64>().ok())
+        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+        .filter(|timeout| !timeout.is_zero())
+        .ok_or_else(|| {
+            format!(
+                "invalid value '{}' for '--timeout': expected a positive number of seconds",
+                value.to_string_lossy()
+            )
+        })
+}
+
+/// Returns the part of `arg` starting at byte offset `start`.
+fn split_inline_value(arg: &OsString, start: usize) -> OsString {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::{OsStrExt, OsStringExt};
+        OsString::from_vec(arg.as_bytes()[start..].to_vec())
+    }
+    #[cfg(not(unix))]
+    {
+        OsString::from(&arg.to_string_lossy()[start..])
+    }
+}
+
+/// Maps a library error onto one of the documented exit codes.
+fn exit_code_for(error: &DittoError) -> u8 {
+    match error.kind() {
+        ErrorKind::Input => EXIT_INPUT,
+        ErrorKind::Output => EXIT_OUTPUT,
+        ErrorKind::Usage => EXIT_USAGE,
+        ErrorKind::Unavailable => EXIT_CONVERTER_UNAVAILABLE,
+        ErrorKind::Timeout => EXIT_TIMEOUT,
+        ErrorKind::Failed => EXIT_CONVERSION_FAILED,
+    }
+}
+
+fn main() -> ExitCode {
+    let command = match parse_args(std::env::args_os().skip(1)) {
+        Ok(command) => command,
+        Err(message) => {
+            eprintln!("ditto: {}\n\n{}", message, USAGE);
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+
+    let (input, output, format, pdf_options, timeout, soffice, overwrite) = match command {
+        Command::Serve {
+            listen,
+            threads,
+            timeout,
+            soffice,
+            max_upload_size,
+        } => return serve(&listen, threads, timeout, soffice, max_upload_size),
+        Command::Help => {
+            print!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Command::Version => {
+            println!("ditto {}", env!("CARGO_PKG_VERSION"));
+            return ExitCode::SUCCESS;
+        }
+        Command::Convert {
+            input,
+            output,
+            format,
+            pdf_options,
+            timeout,
+            soffice,
+            overwrite,
+        } => (
+            input,
+            output,
+            format,
+            pdf_options,
+            timeout,
+            soffice,
+            overwrite,
+        ),
+    };
+
+    let mut converter = Converter::new()
+        .pdf_options(pdf_options)
+        .overwrite(overwrite);
+    if let Some(timeout) = timeout {
+        converter = converter.timeout(timeout);
+    }
+    if let Some(soffice) = soffice {
+        converter = converter.program(soffice);
+    }
+
+    match converter.convert(&input, &output, format) {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("ditto: {}", e);
+            ExitCode::from(exit_code_for(&e))
+        }
+    }
+}
+
+/// Runs `ditto serve` until the process is killed.
+#[cfg(feature = "server")]
+fn serve(
+    listen: &str,
+    threads: Option<usize>,
+    timeout: Option<Duration>,
+    soffice: Option<PathBuf>,
+    max_upload_size: Option<u64>,
+) -> ExitCode {
+    let mut converter = Converter::new();
+    if let Some(timeout) = timeout {
+        converter = converter.timeout(timeout);
+    }
+    if let Some(soffice) = soffice {
+        converter = converter.program(soffice);
+    }
+    let mut server = match ditto::server::Server::bind(listen, converter) {
+        Ok(server) => server,
+        Err(e) => {
+            eprintln!("ditto: cannot listen on {}: {}", listen, e);
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+    if let Some(threads) = threads {
+        server = server.threads(threads);
+    }
+    if let Some(bytes) = max_upload_size {
+        server = server.max_upload_size(bytes);
+    }
+    let address = server
+        .local_addr()
+        .map_or_else(|| listen.to_string(), |address| address.to_string());
+    eprintln!("ditto: listening on http://{}", address);
+    server.run();
+    ExitCode::SUCCESS
+}
+
+#[cfg(not(feature = "server"))]
+fn serve(
+    _listen: &str,
+    _threads: Option<usize>,
+    _timeout: Option<Duration>,
+    _soffice: Option<PathBuf>,
+    _max_upload_size: Option<u64>,
+) -> ExitCode {
+    eprintln!("ditto: this build does not include the server; rebuild with `--features server`");
+    ExitCode::from(EXIT_USAGE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(values: &[&str]) -> Vec<OsString> {
+        values.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn test_parse_args_input_and_output() {
+        let command = parse_args(args(&["--input", "a.docx", "-o", "b.pdf"]));
+        assert_eq!(
+            command,
+            Ok(Command::Convert {
+                input: PathBuf::from("a.docx"),
+                output: PathBuf::from("b.pdf"),
+                format: OutputFormat::Pdf,
+                pdf_options: PdfExportOptions::new(),
+                timeout: None,
+                soffice: None,
+                overwrite: true,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_args_defaults_output_and_accepts_inline_values() {
+        let command = parse_args(args(&[
+            "--input=dir/report.docx",
+            "--to=ODT",
+            "--timeout=1.5",
+            "--soffice=/opt/lo/soffice",
+            "--no-overwrite",
+        ]));
+        assert_eq!(
+            command,
+            Ok(Command::Convert {
+                input: PathBuf::from("dir/report.docx"),
+                output: PathBuf::from("dir/report.odt"),
+                format: OutputFormat::Odt,
+                pdf_options: PdfExportOptions::new(),
+                timeout: Some(Duration::from_millis(1500)),
+                soffice: Some(PathBuf::from("/opt/lo/soffice")),
+                overwrite: false,
+            })
+        );
+    }
+
+    #[test]
+    fn test_default_output_that_is_the_input_is_rejected() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        for (name, to) in [("a.docx", Some("docx")), ("x.pdf", None)] {
+            let path = temp_dir.path().join(name);
+            std::fs::write(&path, "Test content").unwrap();
+            let mut values = vec![OsString::from("-i"), path.clone().into()];
+            values.extend(to.map(|to| OsString::from(format!("--to={}", to))));
+            let Ok(Command::Convert {
+                input,
+                output,
+                format,
+                ..
+            }) = parse_args(values)
+            else {
+                panic!("{} did not parse", name);
+            };
+            assert_eq!(output, input);
+
+            let error = Converter::new()
+                .program(temp_dir.path().join("soffice"))
+                .convert(&input, &output, format)
+                .unwrap_err();
+            assert!(matches!(error, DittoError::OutputIsInput(_)));
+            assert_eq!(exit_code_for(&error), EXIT_USAGE);
+            assert_eq!(std::fs::read(&path).unwrap(), b"Test content");
+        }
+    }
+
+    #[test]
+    fn test_parse_args_pdf_options() {
+        let command = parse_args(args(&["-i", "a.docx", "--pdfa", "2b", "--pages=1-3"]));
+        match command {
+            Ok(Command::Convert { pdf_options, .. }) => assert_eq!(
+                pdf_options,
+                PdfExportOptions::new().pdfa(PdfA::A2b).page_range("1-3")
+            ),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_args_rejects_bad_usage() {
+        assert!(parse_args(args(&[])).is_err());
+        assert!(parse_args(args(&["--input"])).is_err());
+        assert!(parse_args(args(&["-i", "a", "-i", "b"])).is_err());
+        assert!(parse_args(args(&["--bogus"])).is_err());
+        assert!(parse_args(args(&["-i", "a", "--timeout", "0"])).is_err());
+        assert!(parse_args(args(&["-i", "a", "--timeout", "soon"])).is_err());
+        assert!(parse_args(args(&["-i", "a", "--to", "exe"])).is_err());
+        assert!(parse_args(args(&["-i", "a", "--pdfa", "4"])).is_err());
+        assert_eq!(parse_args(args(&["-h", "--bogus"])), Ok(Command::Help));
+    }
+
+    #[test]
+    fn test_parse_serve_args() {
+        assert_eq!(
+            parse_args(args(&["serve"])),
+            Ok(Command::Serve {
+                listen: DEFAULT_LISTEN.to_string(),
+                threads: None,
+                timeout: None,
+                soffice: None,
+                max_upload_size: None,
+            })
+        );
+        assert_eq!(
+            parse_args(args(&[
+                "serve",
+                "--listen=0.0.0.0:8080",
+                "--threads",
+                "4",
+                "-t",
+                "60",
+                "--max-upload-size=1048576",
+            ])),
+            Ok(Command::Serve {
+                listen: "0.0.0.0:8080".to_string(),
+                threads: Some(4),
+                timeout: Some(Duration::from_secs(60)),
+                soffice: None,
+                max_upload_size: Some(1048576),
+            })
+        );
+        assert!(parse_args(args(&["serve", "--threads", "0"])).is_err());
+        assert!(parse_args(args(&["serve", "--input", "a.docx"])).is_err());
+        assert!(parse_args(args(&["-i", "serve"])).is_ok());
+    }
 }
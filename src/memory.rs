@@ -0,0 +1,199 @@
+//! Converting documents held in memory or read from streams rather than
+//! stored in files.
+//!
+//! LibreOffice only works on files, so the input is written to a private
+//! temporary directory, converted there and read back. The directory is
+//! removed when the conversion returns, fails or panics.
+
+use crate::{Converter, DittoError, OutputFormat, Result, StreamReport};
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+impl Converter {
+    /// Converts the document in `input` into `format` and returns the
+    /// converted bytes.
+    ///
+    /// `extension` is the extension the input would have on disk (`"docx"`,
+    /// `"xlsx"`, `"csv"`, ...), without the dot. LibreOffice relies on it for
+    /// formats it cannot recognise by content, and the input check compares
+    /// it against the contents like it does for files.
+    ///
+    /// # Errors
+    /// Fails with [`DittoError::InvalidOptions`] if `extension` is empty or
+    /// not alphanumeric, with [`DittoError::InputTooLarge`] if `input`
+    /// exceeds [`Converter::max_input_size`], and otherwise like
+    /// [`Converter::convert`].
+    ///
+    /// # Example
+    /// ```no_run
+    /// use ditto::{Converter, OutputFormat};
+    ///
+    /// let docx = std::fs::read("report.docx")?;
+    /// let pdf = Converter::new().convert_bytes(&docx, "docx", OutputFormat::Pdf)?;
+    /// # Ok::<(), ditto::DittoError>(())
+    /// ```
+    pub fn convert_bytes(
+        &self,
+        input: &[u8],
+        extension: &str,
+        format: OutputFormat,
+    ) -> Result<Vec<u8>> {
+        let mut output = Vec::new();
+        self.convert_stream(input, &mut output, extension, format)?;
+        Ok(output)
+    }
+
+    /// Converts the document read from `reader` into `format`, writing the
+    /// result to `writer`.
+    ///
+    /// The input is read to the end before LibreOffice starts, and the
+    /// output is written once it has finished. `extension` names the input
+    /// format as for [`Converter::convert_bytes`].
+    ///
+    /// # Errors
+    /// Same as [`Converter::convert_bytes`]. Failing to read the input or to
+    /// write the output is [`DittoError::Io`].
+    ///
+    /// # Example
+    /// ```no_run
+    /// use std::fs::File;
+    /// use ditto::{Converter, OutputFormat};
+    ///
+    /// let upload = File::open("upload.docx")?;
+    /// let mut response = Vec::new();
+    /// let report = Converter::new()
+    ///     .max_input_size(50 * 1024 * 1024)
+    ///     .convert_stream(upload, &mut response, "docx", OutputFormat::Pdf)?;
+    /// assert_eq!(report.bytes_written, response.len() as u64);
+    /// # Ok::<(), ditto::DittoError>(())
+    /// ```
+    pub fn convert_stream<R: Read, W: Write>(
+        &self,
+        reader: R,
+        writer: W,
+        extension: &str,
+        format: OutputFormat,
+    ) -> Result<StreamReport> {
+        self.convert_stream_in(&std::env::temp_dir(), reader, writer, extension, format)
+    }
+
+    /// [`Converter::convert_stream`], with the temporary directory in `parent`.
+    fn convert_stream_in<R: Read, W: Write>(
+        &self,
+        parent: &Path,
+        mut reader: R,
+        mut writer: W,
+        extension: &str,
+        format: OutputFormat,
+    ) -> Result<StreamReport> {
+        let extension = extension.trim_start_matches('.');
+        if extension.is_empty() || !extension.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(DittoError::InvalidOptions(format!(
+                "invalid input extension '{}'",
+                extension
+            )));
+        }
+
+        // Removed on drop, including while unwinding from a panic.
+        let work_dir = tempfile::Builder::new()
+            .prefix("ditto-stream-")
+            .tempdir_in(parent)?;
+        let input_path = work_dir.path().join(format!("input.{}", extension));
+        let output_path = work_dir
+            .path()
+            .join(format!("output.{}", format.extension()));
+
+        let mut input = std::fs::File::create(&input_path)?;
+        let bytes_read = match self.max_input_size {
+            // Read one byte past the limit to tell "exactly at" from "over".
+            Some(limit) => {
+                let copied =
+                    io::copy(&mut (&mut reader).take(limit.saturating_add(1)), &mut input)?;
+                if copied > limit {
+                    return Err(DittoError::InputTooLarge { limit });
+                }
+                copied
+            }
+            None => io::copy(&mut reader, &mut input)?,
+        };
+        drop(input);
+
+        let report = self.convert(&input_path, &output_path, format)?;
+        let bytes_written = io::copy(&mut std::fs::File::open(&output_path)?, &mut writer)?;
+        writer.flush()?;
+
+        Ok(StreamReport {
+            bytes_read,
+            bytes_written,
+            stdout: report.stdout,
+            stderr: report.stderr,
+            attempts: report.attempts,
+        })
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::test_support::{fake_soffice, write_input, CONVERTING_SCRIPT};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_convert_stream_enforces_max_input_size() {
+        let temp_dir = tempdir().unwrap();
+        let soffice = fake_soffice(temp_dir.path(), CONVERTING_SCRIPT);
+        let docx = std::fs::read(write_input(temp_dir.path(), "report.docx")).unwrap();
+        let size = docx.len() as u64;
+
+        let mut pdf = Vec::new();
+        let report = Converter::new()
+            .program(&soffice)
+            .max_input_size(size)
+            .convert_stream(&docx[..], &mut pdf, "docx", OutputFormat::Pdf)
+            .unwrap();
+        assert_eq!(report.bytes_read, size);
+        assert_eq!(report.bytes_written, pdf.len() as u64);
+        assert!(report.stdout.starts_with("convert "));
+
+        let mut pdf = Vec::new();
+        let result = Converter::new()
+            .program(&soffice)
+            .max_input_size(size - 1)
+            .convert_stream(&docx[..], &mut pdf, "docx", OutputFormat::Pdf);
+        assert!(matches!(
+            result,
+            Err(DittoError::InputTooLarge { limit }) if limit == size - 1
+        ));
+        assert!(pdf.is_empty());
+    }
+
+    #[test]
+    fn test_convert_stream_round_trip_and_cleanup() {
+        let temp_dir = tempdir().unwrap();
+        let soffice = fake_soffice(temp_dir.path(), CONVERTING_SCRIPT);
+        let docx = std::fs::read(write_input(temp_dir.path(), "report.docx")).unwrap();
+        let work = temp_dir.path().join("work");
+        std::fs::create_dir(&work).unwrap();
+        let converter = Converter::new().program(&soffice);
+
+        let mut pdf = Vec::new();
+        converter
+            .convert_stream_in(&work, &docx[..], &mut pdf, "docx", OutputFormat::Pdf)
+            .unwrap();
+        assert_eq!(pdf, b"%PDF-1.4 converted from input.docx\n");
+        assert_eq!(std::fs::read_dir(&work).unwrap().count(), 0);
+
+        let result = converter.convert_stream_in(
+            &work,
+            &b"plain text"[..],
+            Vec::new(),
+            "docx",
+            OutputFormat::Pdf,
+        );
+        assert!(matches!(result, Err(DittoError::UnrecognizedInput { .. })));
+        assert_eq!(std::fs::read_dir(&work).unwrap().count(), 0);
+
+        let result = converter.convert_bytes(&docx, "../docx", OutputFormat::Pdf);
+        assert!(matches!(result, Err(DittoError::InvalidOptions(_))));
+    }
+}
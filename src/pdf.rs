@@ -0,0 +1,519 @@
+//! Options for LibreOffice's PDF export filter.
+
+use crate::format::OutputFormat;
+use crate::secret::Secret;
+use crate::sniff::DocumentFamily;
+use crate::{DittoError, Result};
+use std::fmt::Write as _;
+
+/// PDF/A conformance levels for archival output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PdfA {
+    /// PDF/A-1b (ISO 19005-1, based on PDF 1.4).
+    A1b,
+    /// PDF/A-2b (ISO 19005-2, based on PDF 1.7).
+    A2b,
+    /// PDF/A-3b (ISO 19005-3, allows embedded files).
+    A3b,
+}
+
+impl PdfA {
+    /// The `SelectPdfVersion` value LibreOffice uses for this level.
+    fn version(self) -> u32 {
+        match self {
+            PdfA::A1b => 1,
+            PdfA::A2b => 2,
+            PdfA::A3b => 3,
+        }
+    }
+
+    /// Parses `"1"`, `"2b"`, `"PDF/A-3b"` and similar spellings.
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim().to_ascii_lowercase();
+        let value = value
+            .trim_start_matches("pdf/a")
+            .trim_start_matches('-')
+            .trim_end_matches('b');
+        match value {
+            "1" => Some(PdfA::A1b),
+            "2" => Some(PdfA::A2b),
+            "3" => Some(PdfA::A3b),
+            _ => None,
+        }
+    }
+}
+
+/// Whether an encrypted PDF may be printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PrintPermission {
+    /// Printing is not allowed.
+    None,
+    /// Only low-resolution (150 DPI) printing is allowed.
+    LowResolution,
+    /// Printing is allowed at full quality.
+    HighResolution,
+}
+
+/// Which changes may be made to an encrypted PDF.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChangePermission {
+    /// No changes are allowed.
+    None,
+    /// Pages may be inserted, deleted and rotated.
+    InsertDeleteRotatePages,
+    /// Form fields may be filled in.
+    FillForms,
+    /// Form fields may be filled in and comments added.
+    CommentAndFillForms,
+    /// Any change except extracting pages.
+    Any,
+}
+
+/// What readers of an encrypted PDF may do without the owner password.
+///
+/// The default allows everything; restrict individual actions with the
+/// builder methods.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PdfPermissions {
+    printing: PrintPermission,
+    changes: ChangePermission,
+    copying: bool,
+    accessibility: bool,
+}
+
+impl Default for PdfPermissions {
+    fn default() -> Self {
+        PdfPermissions {
+            printing: PrintPermission::HighResolution,
+            changes: ChangePermission::Any,
+            copying: true,
+            accessibility: true,
+        }
+    }
+}
+
+impl PdfPermissions {
+    /// Permissions that allow everything.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Permissions that allow nothing beyond reading, except text access
+    /// for accessibility tools.
+    pub fn read_only() -> Self {
+        PdfPermissions {
+            printing: PrintPermission::None,
+            changes: ChangePermission::None,
+            copying: false,
+            accessibility: true,
+        }
+    }
+
+    /// Sets whether and how the document may be printed.
+    pub fn printing(mut self, printing: PrintPermission) -> Self {
+        self.printing = printing;
+        self
+    }
+
+    /// Sets which changes may be made to the document.
+    pub fn changes(mut self, changes: ChangePermission) -> Self {
+        self.changes = changes;
+        self
+    }
+
+    /// Sets whether text and images may be copied.
+    pub fn copying(mut self, allowed: bool) -> Self {
+        self.copying = allowed;
+        self
+    }
+
+    /// Sets whether accessibility tools may extract the text.
+    pub fn accessibility(mut self, allowed: bool) -> Self {
+        self.accessibility = allowed;
+        self
+    }
+}
+
+/// Settings passed to LibreOffice's PDF export filter.
+///
+/// Anything left unset keeps LibreOffice's default. The options only apply
+/// when converting to [`OutputFormat::Pdf`](crate::OutputFormat::Pdf).
+///
+/// # Example
+/// ```no_run
+/// use std::path::Path;
+/// use ditto::{Converter, PdfA, PdfExportOptions};
+///
+/// let options = PdfExportOptions::new().pdfa(PdfA::A2b).page_range("1-3");
+/// Converter::new()
+///     .pdf_options(options)
+///     .docx_to_pdf(Path::new("contract.docx"), Path::new("contract.pdf"))?;
+/// # Ok::<(), ditto::DittoError>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PdfExportOptions {
+    pdfa: Option<PdfA>,
+    pdf_ua: Option<bool>,
+    page_range: Option<String>,
+    jpeg_quality: Option<u8>,
+    lossless_images: Option<bool>,
+    max_image_resolution: Option<u32>,
+    export_bookmarks: Option<bool>,
+    export_notes: Option<bool>,
+    tagged: Option<bool>,
+    open_password: Option<Secret>,
+    owner_password: Option<Secret>,
+    permissions: Option<PdfPermissions>,
+}
+
+/// Image resolutions (in DPI) LibreOffice accepts for downsampling.
+const IMAGE_RESOLUTIONS: [u32; 5] = [75, 150, 300, 600, 1200];
+
+impl PdfExportOptions {
+    /// Creates options that keep every LibreOffice default.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Produces a PDF/A document of the given conformance level.
+    pub fn pdfa(mut self, level: PdfA) -> Self {
+        self.pdfa = Some(level);
+        self
+    }
+
+    /// Produces a PDF/UA (universal accessibility) compliant document.
+    pub fn pdf_ua(mut self, enabled: bool) -> Self {
+        self.pdf_ua = Some(enabled);
+        self
+    }
+
+    /// Exports only the given pages, e.g. `"1-3,5,7-"`.
+    pub fn page_range(mut self, range: impl Into<String>) -> Self {
+        self.page_range = Some(range.into());
+        self
+    }
+
+    /// Compresses images as JPEG with the given quality (1-100).
+    pub fn jpeg_quality(mut self, quality: u8) -> Self {
+        self.jpeg_quality = Some(quality);
+        self
+    }
+
+    /// Compresses images losslessly instead of as JPEG.
+    pub fn lossless_images(mut self, enabled: bool) -> Self {
+        self.lossless_images = Some(enabled);
+        self
+    }
+
+    /// Downsamples images to at most `dpi` (one of 75, 150, 300, 600 or 1200).
+    pub fn reduce_image_resolution(mut self, dpi: u32) -> Self {
+        self.max_image_resolution = Some(dpi);
+        self
+    }
+
+    /// Whether headings are exported as PDF bookmarks.
+    pub fn export_bookmarks(mut self, enabled: bool) -> Self {
+        self.export_bookmarks = Some(enabled);
+        self
+    }
+
+    /// Whether comments are exported as PDF annotations.
+    pub fn export_notes(mut self, enabled: bool) -> Self {
+        self.export_notes = Some(enabled);
+        self
+    }
+
+    /// Whether the PDF is tagged with the document structure.
+    pub fn tagged(mut self, enabled: bool) -> Self {
+        self.tagged = Some(enabled);
+        self
+    }
+
+    /// Encrypts the PDF so that it can only be opened with `password`.
+    ///
+    /// Encrypted PDFs are exported over UNO by the helper script described
+    /// at [`Converter::python`](crate::Converter::python), which is handed
+    /// the passwords through its environment: they never appear on a
+    /// command line.
+    pub fn open_password(mut self, password: impl Into<String>) -> Self {
+        self.open_password = Some(Secret::new(password));
+        self
+    }
+
+    /// Sets the owner (permissions) password, which is needed to lift the
+    /// restrictions set with [`PdfExportOptions::permissions`].
+    pub fn owner_password(mut self, password: impl Into<String>) -> Self {
+        self.owner_password = Some(Secret::new(password));
+        self
+    }
+
+    /// Restricts what readers may do with the PDF. Requires an owner password.
+    pub fn permissions(mut self, permissions: PdfPermissions) -> Self {
+        self.permissions = Some(permissions);
+        self
+    }
+
+    /// Whether every option is left at LibreOffice's default.
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Whether the options set a password, which must not be passed to
+    /// `soffice --convert-to`.
+    pub(crate) fn is_encrypted(&self) -> bool {
+        self.open_password.is_some() || self.owner_password.is_some()
+    }
+
+    /// Checks that every option holds a value LibreOffice accepts.
+    pub fn validate(&self) -> Result<()> {
+        if let Some(range) = &self.page_range {
+            let valid = !range.trim().is_empty()
+                && range
+                    .chars()
+                    .all(|c| c.is_ascii_digit() || matches!(c, ',' | '-' | ' ' | ';'));
+            if !valid {
+                return Err(DittoError::InvalidOptions(format!(
+                    "invalid page range '{}'",
+                    range
+                )));
+            }
+        }
+        if let Some(quality) = self.jpeg_quality {
+            if !(1..=100).contains(&quality) {
+                return Err(DittoError::InvalidOptions(format!(
+                    "JPEG quality must be between 1 and 100, got {}",
+                    quality
+                )));
+            }
+        }
+        if self.is_encrypted() && self.pdfa.is_some() {
+            return Err(DittoError::InvalidOptions(
+                "PDF/A documents cannot be encrypted".to_string(),
+            ));
+        }
+        if self.permissions.is_some() && self.owner_password.is_none() {
+            return Err(DittoError::InvalidOptions(
+                "restricting PDF permissions requires an owner password".to_string(),
+            ));
+        }
+        if let Some(dpi) = self.max_image_resolution {
+            if !IMAGE_RESOLUTIONS.contains(&dpi) {
+                return Err(DittoError::InvalidOptions(format!(
+                    "image resolution must be one of {:?} DPI, got {}",
+                    IMAGE_RESOLUTIONS, dpi
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// The filter options in LibreOffice's JSON syntax, e.g.
+    /// `{"SelectPdfVersion":{"type":"long","value":"2"}}`.
+    pub(crate) fn filter_options(&self) -> String {
+        let mut options = FilterOptions::default();
+        if let Some(level) = self.pdfa {
+            options.long("SelectPdfVersion", level.version());
+        }
+        options.boolean("PDFUACompliance", self.pdf_ua);
+        if let Some(range) = &self.page_range {
+            options.string("PageRange", range);
+        }
+        if let Some(quality) = self.jpeg_quality {
+            options.long("Quality", quality as u32);
+        }
+        options.boolean("UseLosslessCompression", self.lossless_images);
+        if let Some(dpi) = self.max_image_resolution {
+            options.boolean("ReduceImageResolution", Some(true));
+            options.long("MaxImageResolution", dpi);
+        }
+        options.boolean("ExportBookmarks", self.export_bookmarks);
+        options.boolean("ExportNotes", self.export_notes);
+        options.boolean("UseTaggedPDF", self.tagged);
+        if let Some(password) = &self.open_password {
+            options.boolean("EncryptFile", Some(true));
+            options.string("DocumentOpenPassword", password.expose());
+        }
+        if let Some(password) = &self.owner_password {
+            let permissions = self.permissions.unwrap_or_default();
+            options.boolean("RestrictPermissions", Some(true));
+            options.string("PermissionPassword", password.expose());
+            options.long(
+                "Printing",
+                match permissions.printing {
+                    PrintPermission::None => 0,
+                    PrintPermission::LowResolution => 1,
+                    PrintPermission::HighResolution => 2,
+                },
+            );
+            options.long(
+                "Changes",
+                match permissions.changes {
+                    ChangePermission::None => 0,
+                    ChangePermission::InsertDeleteRotatePages => 1,
+                    ChangePermission::FillForms => 2,
+                    ChangePermission::CommentAndFillForms => 3,
+                    ChangePermission::Any => 4,
+                },
+            );
+            options.boolean("EnableCopyingOfContent", Some(permissions.copying));
+            options.boolean(
+                "EnableTextAccessForAccessibilityTools",
+                Some(permissions.accessibility),
+            );
+        }
+        options.finish()
+    }
+
+    /// The value passed to `soffice --convert-to` for a document of `family`.
+    ///
+    /// Never used for encrypted output, whose passwords it would expose.
+    pub(crate) fn convert_to_arg(&self, family: DocumentFamily) -> String {
+        debug_assert!(!self.is_encrypted(), "PDF passwords on a command line");
+        let (filter, _) = OutputFormat::Pdf.export_filter(family);
+        format!("pdf:{}:{}", filter, self.filter_options())
+    }
+}
+
+/// Builds the JSON object LibreOffice accepts as filter options.
+#[derive(Default)]
+pub(crate) struct FilterOptions {
+    json: String,
+}
+
+impl FilterOptions {
+    fn property(&mut self, name: &str, kind: &str, value: &str) {
+        self.json.push(if self.json.is_empty() { '{' } else { ',' });
+        let _ = write!(
+            self.json,
+            "\"{}\":{{\"type\":\"{}\",\"value\":\"{}\"}}",
+            name,
+            kind,
+            escape_json(value)
+        );
+    }
+
+    pub fn boolean(&mut self, name: &str, value: Option<bool>) {
+        if let Some(value) = value {
+            self.property(name, "boolean", if value { "true" } else { "false" });
+        }
+    }
+
+    pub fn long(&mut self, name: &str, value: u32) {
+        self.property(name, "long", &value.to_string());
+    }
+
+    pub fn string(&mut self, name: &str, value: &str) {
+        self.property(name, "string", value);
+    }
+
+    pub fn finish(mut self) -> String {
+        if self.json.is_empty() {
+            self.json.push('{');
+        }
+        self.json.push('}');
+        self.json
+    }
+}
+
+fn escape_json(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => {
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_options_serialization() {
+        let options = PdfExportOptions::new()
+            .pdfa(PdfA::A2b)
+            .page_range("2-3")
+            .jpeg_quality(85)
+            .reduce_image_resolution(300)
+            .tagged(true)
+            .export_notes(false);
+        assert_eq!(
+            options.convert_to_arg(DocumentFamily::Text),
+            "pdf:writer_pdf_Export:{\
+             \"SelectPdfVersion\":{\"type\":\"long\",\"value\":\"2\"},\
+             \"PageRange\":{\"type\":\"string\",\"value\":\"2-3\"},\
+             \"Quality\":{\"type\":\"long\",\"value\":\"85\"},\
+             \"ReduceImageResolution\":{\"type\":\"boolean\",\"value\":\"true\"},\
+             \"MaxImageResolution\":{\"type\":\"long\",\"value\":\"300\"},\
+             \"ExportNotes\":{\"type\":\"boolean\",\"value\":\"false\"},\
+             \"UseTaggedPDF\":{\"type\":\"boolean\",\"value\":\"true\"}}"
+        );
+        assert_eq!(
+            PdfExportOptions::new().convert_to_arg(DocumentFamily::Spreadsheet),
+            "pdf:calc_pdf_Export:{}"
+        );
+    }
+
+    #[test]
+    fn test_encryption_filter_options() {
+        let options = PdfExportOptions::new()
+            .open_password("open \"sesame\"")
+            .owner_password("owner")
+            .permissions(PdfPermissions::read_only().printing(PrintPermission::LowResolution));
+        assert!(options.validate().is_ok());
+        assert_eq!(
+            options.filter_options(),
+            "{\
+             \"EncryptFile\":{\"type\":\"boolean\",\"value\":\"true\"},\
+             \"DocumentOpenPassword\":{\"type\":\"string\",\"value\":\"open \\\"sesame\\\"\"},\
+             \"RestrictPermissions\":{\"type\":\"boolean\",\"value\":\"true\"},\
+             \"PermissionPassword\":{\"type\":\"string\",\"value\":\"owner\"},\
+             \"Printing\":{\"type\":\"long\",\"value\":\"1\"},\
+             \"Changes\":{\"type\":\"long\",\"value\":\"0\"},\
+             \"EnableCopyingOfContent\":{\"type\":\"boolean\",\"value\":\"false\"},\
+             \"EnableTextAccessForAccessibilityTools\":{\"type\":\"boolean\",\"value\":\"true\"}}"
+        );
+        assert!(!format!("{:?}", options).contains("sesame"));
+    }
+
+    #[test]
+    fn test_validate_rejects_inconsistent_encryption() {
+        let restricted = PdfExportOptions::new().permissions(PdfPermissions::read_only());
+        assert!(restricted.validate().is_err());
+        let archived = PdfExportOptions::new()
+            .pdfa(PdfA::A2b)
+            .open_password("secret");
+        assert!(archived.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_rejects_out_of_range_values() {
+        assert!(PdfExportOptions::new()
+            .page_range("1-3, 5")
+            .validate()
+            .is_ok());
+        assert!(PdfExportOptions::new()
+            .page_range("all")
+            .validate()
+            .is_err());
+        assert!(PdfExportOptions::new().jpeg_quality(0).validate().is_err());
+        assert!(PdfExportOptions::new()
+            .reduce_image_resolution(200)
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn test_pdfa_parse() {
+        assert_eq!(PdfA::parse("2"), Some(PdfA::A2b));
+        assert_eq!(PdfA::parse("PDF/A-3b"), Some(PdfA::A3b));
+        assert_eq!(PdfA::parse("1b"), Some(PdfA::A1b));
+        assert_eq!(PdfA::parse("4"), None);
+    }
+}
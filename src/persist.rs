@@ -0,0 +1,216 @@
+//! Moving converted files into place.
+//!
+//! LibreOffice writes the converted file into a staging directory, by
+//! default on the same filesystem as the output. Only once it is complete is
+//! it renamed to the output path, so readers see either the previous file or
+//! the whole new one, never a partially written document. When the staging
+//! directory is on another filesystem (see
+//! [`Converter::scratch_dir`](crate::Converter::scratch_dir)), the file is
+//! first copied next to the output and renamed from there.
+
+use crate::{DittoError, Result};
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::Path;
+
+/// Moves the finished file `staged` to `destination`.
+///
+/// The data is flushed to disk first, so that a crash right after the rename
+/// cannot leave an empty or truncated file behind, and on Unix the directory
+/// is flushed after it, so that the rename itself survives a crash. Without
+/// `overwrite`, an
+/// existing `destination` is never replaced and the move fails with
+/// [`DittoError::OutputExists`]. Other failures are reported as
+/// [`DittoError::OutputNotWritten`].
+pub(crate) fn persist(staged: &Path, destination: &Path, overwrite: bool) -> Result<()> {
+    // Flushing needs write access on Windows.
+    let result = OpenOptions::new()
+        .write(true)
+        .open(staged)
+        .and_then(|file| file.sync_all())
+        .and_then(|()| place(staged, destination, overwrite));
+    match result {
+        Err(error) if error.kind() == io::ErrorKind::CrossesDevices => {
+            copy_across(staged, destination, overwrite)?
+        }
+        result => result.map_err(|error| not_written(destination, error))?,
+    }
+    sync_dir(parent_dir(destination)).map_err(|error| not_written(destination, error))
+}
+
+/// The directory `path` is in.
+fn parent_dir(path: &Path) -> &Path {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    }
+}
+
+/// Flushes the entries of `dir` to disk, making a rename into it durable.
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    File::open(dir)?.sync_all()
+}
+
+/// Windows has no way to flush a directory; renames are journaled by NTFS.
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Renames `from` to `to` on the same filesystem. Without `overwrite`, fails
+/// with [`io::ErrorKind::AlreadyExists`] if `to` exists.
+fn place(from: &Path, to: &Path, overwrite: bool) -> io::Result<()> {
+    if overwrite {
+        return fs::rename(from, to);
+    }
+    // Linking fails if the destination exists, which makes the check and the
+    // move a single atomic step.
+    match fs::hard_link(from, to) {
+        Ok(()) => fs::remove_file(from),
+        Err(error)
+            if matches!(
+                error.kind(),
+                io::ErrorKind::AlreadyExists | io::ErrorKind::CrossesDevices
+            ) =>
+        {
+            Err(error)
+        }
+        // Filesystems without hard links: check, then rename.
+        Err(_) if to.exists() => Err(io::ErrorKind::AlreadyExists.into()),
+        Err(_) => fs::rename(from, to),
+    }
+}
+
+/// Moves `staged` to `destination` on another filesystem: copies it into a
+/// temporary file next to `destination`, flushes it, renames that into
+/// place and finally removes `staged`.
+fn copy_across(staged: &Path, destination: &Path, overwrite: bool) -> Result<()> {
+    let dir = parent_dir(destination);
+    let mut builder = tempfile::Builder::new();
+    builder.prefix(".ditto-");
+    // Temporary files are private by default; the output should get the
+    // same permissions as a file created normally (subject to the umask).
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        builder.permissions(fs::Permissions::from_mode(0o666));
+    }
+    // Removed again if anything below fails.
+    let mut copy = builder
+        .tempfile_in(dir)
+        .map_err(|error| not_written(destination, error))?;
+    File::open(staged)
+        .and_then(|mut file| io::copy(&mut file, copy.as_file_mut()))
+        .and_then(|_| copy.as_file().sync_all())
+        .and_then(|()| place(copy.path(), destination, overwrite))
+        .map_err(|error| not_written(destination, error))?;
+    // The output is in place; the staging directory removes `staged` anyway.
+    let _ = fs::remove_file(staged);
+    Ok(())
+}
+
+fn not_written(destination: &Path, error: io::Error) -> DittoError {
+    match error.kind() {
+        io::ErrorKind::AlreadyExists => DittoError::OutputExists(destination.to_path_buf()),
+        _ => DittoError::OutputNotWritten {
+            path: destination.to_path_buf(),
+            source: error,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_persist_overwrites_only_when_allowed() {
+        let temp_dir = tempdir().unwrap();
+        let staged = temp_dir.path().join("staged.pdf");
+        let destination = temp_dir.path().join("out.pdf");
+
+        std::fs::write(&staged, "first").unwrap();
+        persist(&staged, &destination, false).unwrap();
+        assert!(!staged.exists());
+        assert_eq!(std::fs::read_to_string(&destination).unwrap(), "first");
+
+        std::fs::write(&staged, "second").unwrap();
+        let result = persist(&staged, &destination, false);
+        assert!(matches!(result, Err(DittoError::OutputExists(path)) if path == destination));
+        assert_eq!(std::fs::read_to_string(&destination).unwrap(), "first");
+
+        persist(&staged, &destination, true).unwrap();
+        assert_eq!(std::fs::read_to_string(&destination).unwrap(), "second");
+    }
+
+    #[test]
+    fn test_copy_across_moves_between_directories() {
+        let scratch = tempdir().unwrap();
+        let output = tempdir().unwrap();
+        let staged = scratch.path().join("report.pdf");
+        let destination = output.path().join("final.pdf");
+
+        std::fs::write(&staged, "converted").unwrap();
+        copy_across(&staged, &destination, false).unwrap();
+        assert!(!staged.exists());
+        assert_eq!(std::fs::read_to_string(&destination).unwrap(), "converted");
+
+        std::fs::write(&staged, "again").unwrap();
+        let result = copy_across(&staged, &destination, false);
+        assert!(matches!(result, Err(DittoError::OutputExists(_))));
+        // Neither the output nor the temporary copy are left changed.
+        assert_eq!(std::fs::read_to_string(&destination).unwrap(), "converted");
+        assert_eq!(std::fs::read_dir(output.path()).unwrap().count(), 1);
+
+        let missing_dir = output.path().join("missing").join("final.pdf");
+        let result = copy_across(&staged, &missing_dir, true);
+        assert!(matches!(result, Err(DittoError::OutputNotWritten { .. })));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_copy_across_keeps_default_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let scratch = tempdir().unwrap();
+        let output = tempdir().unwrap();
+        let staged = scratch.path().join("report.pdf");
+        std::fs::write(&staged, "converted").unwrap();
+        let destination = output.path().join("report.pdf");
+        copy_across(&staged, &destination, true).unwrap();
+
+        let reference = output.path().join("reference.pdf");
+        std::fs::write(&reference, "").unwrap();
+        let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&destination), mode(&reference));
+    }
+
+    /// Exercises a real cross-device move where the machine has a tmpfs
+    /// mounted on a different filesystem than the temporary directory.
+    #[cfg(unix)]
+    #[test]
+    fn test_persist_across_filesystems() {
+        use std::os::unix::fs::MetadataExt;
+
+        let shm = Path::new("/dev/shm");
+        let output = tempdir().unwrap();
+        let Ok(shm_meta) = std::fs::metadata(shm) else {
+            return;
+        };
+        if shm_meta.dev() == std::fs::metadata(output.path()).unwrap().dev() {
+            return;
+        }
+        let Ok(scratch) = tempfile::tempdir_in(shm) else {
+            return;
+        };
+        let staged = scratch.path().join("report.pdf");
+        std::fs::write(&staged, "converted").unwrap();
+        let destination = output.path().join("report.pdf");
+
+        persist(&staged, &destination, true).unwrap();
+        assert!(!staged.exists());
+        assert_eq!(std::fs::read_to_string(&destination).unwrap(), "converted");
+    }
+}
@@ -0,0 +1,382 @@
+//! A pool of long-running LibreOffice processes.
+//!
+//! Starting LibreOffice takes seconds, which dominates the time it takes to
+//! convert a small document. A [`WorkerPool`] keeps a number of `soffice`
+//! processes running as UNO listeners and hands every conversion to an idle
+//! one through the Python helper of [`crate::uno`].
+//!
+//! Workers listen on named pipes rather than TCP ports: a port picked in
+//! advance could be taken by another process before LibreOffice binds it,
+//! and any local user could connect to it and drive LibreOffice, reading
+//! and writing files as the pool's user.
+
+use crate::process::{self, Captured};
+use crate::profile::{Profile, ProfileMode};
+use crate::uno::{self, Job};
+use crate::{ConversionReport, Converter, DittoError, OutputFormat, Result};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Stdio};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::Duration;
+
+/// How a [`WorkerPool`] is sized and maintained.
+///
+/// # Example
+/// ```no_run
+/// use std::path::Path;
+/// use ditto::{Converter, PoolOptions};
+///
+/// let pool = Converter::new().start_pool(PoolOptions::new().workers(4).max_jobs(200))?;
+/// pool.docx_to_pdf(Path::new("in.docx"), Path::new("out.pdf"))?;
+/// # Ok::<(), ditto::DittoError>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolOptions {
+    workers: usize,
+    max_jobs: Option<usize>,
+}
+
+impl Default for PoolOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PoolOptions {
+    /// One worker per available CPU, never recycled.
+    pub fn new() -> Self {
+        PoolOptions {
+            workers: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            max_jobs: None,
+        }
+    }
+
+    /// Runs `workers` LibreOffice processes (at least one).
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.workers = workers.max(1);
+        self
+    }
+
+    /// Restarts a worker after it has run `jobs` conversions, releasing
+    /// whatever memory LibreOffice accumulated.
+    pub fn max_jobs(mut self, jobs: usize) -> Self {
+        self.max_jobs = Some(jobs.max(1));
+        self
+    }
+}
+
+/// Long-lived LibreOffice workers that conversions are dispatched to.
+///
+/// Created by [`Converter::start_pool`], whose configuration (timeout, PDF
+/// options, input password, ...) applies to every conversion. The pool can
+/// be shared between threads, for example in an `Arc`; each conversion
+/// waits for an idle worker. Every worker has its own temporary profile,
+/// whatever [`ProfileMode`] the converter was configured with.
+///
+/// A worker that has exited, cannot be reached or timed out is replaced by a
+/// fresh one. Dropping the pool stops all workers.
+#[derive(Debug)]
+pub struct WorkerPool {
+    converter: Converter,
+    program: PathBuf,
+    python: PathBuf,
+    max_jobs: Option<usize>,
+    idle: Mutex<Vec<Worker>>,
+    available: Condvar,
+}
+
+impl WorkerPool {
+    /// Starts the workers. LibreOffice is still initialising when this
+    /// returns; the first conversions wait for it to be ready.
+    pub(crate) fn start(
+        converter: Converter,
+        program: PathBuf,
+        python: PathBuf,
+        options: PoolOptions,
+    ) -> Result<Self> {
+        let workers = (0..options.workers)
+            .map(|_| {
+                Ok(Worker {
+                    listener: Some(Listener::spawn(&program)?),
+                    jobs: 0,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(WorkerPool {
+            converter,
+            program,
+            python,
+            max_jobs: options.max_jobs,
+            idle: Mutex::new(workers),
+            available: Condvar::new(),
+        })
+    }
+
+    /// Converts a `.docx` file to `.pdf` on one of the workers.
+    ///
+    /// Equivalent to [`WorkerPool::convert`] with [`OutputFormat::Pdf`].
+    pub fn docx_to_pdf(&self, input_path: &Path, output_path: &Path) -> Result<ConversionReport> {
+        self.convert(input_path, output_path, OutputFormat::Pdf)
+    }
+
+    /// Converts any document LibreOffice can open into `format` on one of
+    /// the workers.
+    ///
+    /// Validation and errors are the same as for [`Converter::convert`].
+    pub fn convert(
+        &self,
+        input_path: &Path,
+        output_path: &Path,
+        format: OutputFormat,
+    ) -> Result<ConversionReport> {
+        self.converter
+            .convert_using(input_path, output_path, format, Some(self), None)
+    }
+
+    /// Runs `job` on an idle worker.
+    ///
+    /// A worker that cannot be reached is restarted and the job tried once
+    /// more.
+    pub(crate) fn run(&self, job: &Job<'_>, timeout: Option<Duration>) -> Result<Captured> {
+        let mut worker = self.checkout()?;
+        let mut captured = self.run_on(&worker, job, timeout);
+        if is_unreachable(&captured) {
+            if let Err(error) = self.restart(&mut worker) {
+                self.checkin(worker);
+                return Err(error);
+            }
+            captured = self.run_on(&worker, job, timeout);
+        }
+        worker.jobs += 1;
+
+        // A worker that timed out may still be busy with the document.
+        let timed_out = matches!(&captured, Ok(Captured { status: None, .. }));
+        let worn_out = self.max_jobs.is_some_and(|max| worker.jobs >= max);
+        if timed_out || is_unreachable(&captured) || worn_out {
+            // Should this fail, the next checkout tries again.
+            let _ = self.restart(&mut worker);
+        }
+        self.checkin(worker);
+        captured
+    }
+
+    fn run_on(
+        &self,
+        worker: &Worker,
+        job: &Job<'_>,
+        timeout: Option<Duration>,
+    ) -> Result<Captured> {
+        let listener = worker
+            .listener
+            .as_ref()
+            .expect("checked out workers are running");
+        let helper = uno::helper_command(
+            &self.python,
+            &listener.connection,
+            job,
+            timeout.unwrap_or(uno::DEFAULT_CONNECT_TIMEOUT),
+            false,
+        )?;
+        process::run(helper, timeout)
+            .map_err(|source| DittoError::from_spawn(self.python.clone(), source))
+    }
+
+    /// Takes an idle worker, waiting for one if all are busy, and restarts
+    /// it first if its LibreOffice process is gone.
+    fn checkout(&self) -> Result<Worker> {
+        let mut idle = self.lock();
+        let mut worker = loop {
+            match idle.pop() {
+                Some(worker) => break worker,
+                None => {
+                    idle = self
+                        .available
+                        .wait(idle)
+                        .unwrap_or_else(|poisoned| poisoned.into_inner())
+                }
+            }
+        };
+        drop(idle);
+        if !worker.is_alive() {
+            if let Err(error) = self.restart(&mut worker) {
+                self.checkin(worker);
+                return Err(error);
+            }
+        }
+        Ok(worker)
+    }
+
+    fn checkin(&self, worker: Worker) {
+        self.lock().push(worker);
+        self.available.notify_one();
+    }
+
+    /// Stops the worker's LibreOffice and starts a fresh one. On failure the
+    /// worker is left without a process.
+    fn restart(&self, worker: &mut Worker) -> Result<()> {
+        worker.listener = None;
+        worker.jobs = 0;
+        worker.listener = Some(Listener::spawn(&self.program)?);
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<Worker>> {
+        self.idle
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Whether the helper could not reach the worker's listener.
+fn is_unreachable(captured: &Result<Captured>) -> bool {
+    matches!(captured, Ok(captured) if captured.status.and_then(|status| status.code()) == Some(uno::EXIT_CONNECT))
+}
+
+/// A slot in the pool.
+#[derive(Debug)]
+struct Worker {
+    /// The running LibreOffice, if (re)starting it succeeded.
+    listener: Option<Listener>,
+    /// Conversions run since the listener was started.
+    jobs: usize,
+}
+
+impl Worker {
+    fn is_alive(&mut self) -> bool {
+        match &mut self.listener {
+            Some(listener) => matches!(listener.child.try_wait(), Ok(None)),
+            None => false,
+        }
+    }
+}
+
+/// One LibreOffice listener process, stopped when dropped.
+#[derive(Debug)]
+struct Listener {
+    child: Child,
+    connection: String,
+    // Dropped after the process has been stopped.
+    _profile: Profile,
+}
+
+impl Listener {
+    fn spawn(program: &Path) -> Result<Self> {
+        let profile = Profile::prepare(&ProfileMode::Isolated)?;
+        let connection = uno::unique_connection();
+        let mut command = uno::listener_command(program, &profile, &connection);
+        command.stdout(Stdio::null()).stderr(Stdio::null());
+        let child = process::spawn_group(command)
+            .map_err(|source| DittoError::from_spawn(program.to_path_buf(), source))?;
+        Ok(Listener {
+            child,
+            connection,
+            _profile: profile,
+        })
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        process::kill_tree(&mut self.child);
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::test_support::{fake_program, fake_soffice, write_input};
+    use tempfile::tempdir;
+
+    /// A listener that idles until killed.
+    const LISTENER_SCRIPT: &str = "exec sleep 30\n";
+
+    /// A helper that logs the connection it was given and writes the
+    /// output, but fails to connect once if the file `unreachable` exists
+    /// next to it.
+    const HELPER_SCRIPT: &str = r#"
+here=$(dirname "$0")
+while [ $# -gt 0 ]; do
+  case "$1" in
+    --connect) echo "$2" >> "$here/connections.txt"; shift ;;
+    --output) output="${2#file://}"; shift ;;
+  esac
+  shift
+done
+if [ -e "$here/unreachable" ]; then
+  rm "$here/unreachable"
+  echo "Error: could not connect to LibreOffice" >&2
+  exit 2
+fi
+echo converted > "$output"
+"#;
+
+    fn connections(dir: &Path) -> Vec<String> {
+        std::fs::read_to_string(dir.join("connections.txt"))
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn test_pool_recycles_workers_after_max_jobs() {
+        let temp_dir = tempdir().unwrap();
+        let soffice = fake_soffice(temp_dir.path(), LISTENER_SCRIPT);
+        let python = fake_program(temp_dir.path(), "python", HELPER_SCRIPT);
+        let input = write_input(temp_dir.path(), "report.docx");
+
+        let pool = Converter::new()
+            .program(&soffice)
+            .python(&python)
+            .start_pool(PoolOptions::new().workers(1).max_jobs(2))
+            .unwrap();
+        for i in 0..3 {
+            let output = temp_dir.path().join(format!("report-{}.pdf", i));
+            pool.docx_to_pdf(&input, &output).unwrap();
+            assert_eq!(std::fs::read_to_string(&output).unwrap(), "converted\n");
+        }
+
+        // Two jobs on the first worker, the third on its replacement.
+        let used = connections(temp_dir.path());
+        assert_eq!(used.len(), 3);
+        assert!(used
+            .iter()
+            .all(|connection| connection.starts_with("pipe,")));
+        assert_eq!(used[0], used[1]);
+        assert_ne!(used[1], used[2]);
+    }
+
+    #[test]
+    fn test_pool_replaces_unreachable_worker() {
+        let temp_dir = tempdir().unwrap();
+        let soffice = fake_soffice(temp_dir.path(), LISTENER_SCRIPT);
+        let python = fake_program(temp_dir.path(), "python", HELPER_SCRIPT);
+        let input = write_input(temp_dir.path(), "report.docx");
+        std::fs::write(temp_dir.path().join("unreachable"), "").unwrap();
+
+        let pool = Converter::new()
+            .program(&soffice)
+            .python(&python)
+            .start_pool(PoolOptions::new().workers(1))
+            .unwrap();
+        pool.docx_to_pdf(&input, &temp_dir.path().join("report.pdf"))
+            .unwrap();
+
+        let used = connections(temp_dir.path());
+        assert_eq!(used.len(), 2);
+        assert_ne!(used[0], used[1]);
+    }
+
+    #[test]
+    fn test_pool_missing_program() {
+        let temp_dir = tempdir().unwrap();
+        let missing = temp_dir.path().join("no-such-soffice");
+
+        let result = Converter::new()
+            .program(&missing)
+            .start_pool(PoolOptions::new().workers(2));
+
+        assert!(matches!(result, Err(DittoError::ConverterNotFound { .. })));
+    }
+}
@@ -0,0 +1,251 @@
+//! Running the converter as a child process with an optional timeout.
+
+use std::io::{self, Read};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// How often a running child is polled while waiting for it to exit.
+pub(crate) const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Everything the child printed, plus how it ended.
+#[derive(Debug)]
+pub(crate) struct Captured {
+    /// The exit status, or `None` if the child was killed after timing out.
+    pub status: Option<ExitStatus>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Runs `command` to completion, capturing its output.
+///
+/// The child is placed in its own process group. If `timeout` elapses
+/// before it exits, the whole group is killed, which also takes down the
+/// `soffice.bin` process that the `soffice` launcher spawns.
+pub(crate) fn run(command: Command, timeout: Option<Duration>) -> io::Result<Captured> {
+    run_cancellable(command, timeout, None)
+}
+
+/// Like [`run`], but also killing the child as soon as `cancelled` is set.
+/// A cancelled child is reported like one that timed out.
+pub(crate) fn run_cancellable(
+    mut command: Command,
+    timeout: Option<Duration>,
+    cancelled: Option<&AtomicBool>,
+) -> io::Result<Captured> {
+    command.stdout(Stdio::piped()).stderr(Stdio::piped());
+    let mut child = spawn_group(command)?;
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let status = match (deadline, cancelled) {
+        (None, None) => Some(child.wait()?),
+        _ => wait_until(&mut child, deadline, cancelled)?,
+    };
+    if status.is_none() {
+        kill_tree(&mut child);
+        child.wait()?;
+    }
+
+    Ok(Captured {
+        status,
+        stdout: collect(stdout),
+        stderr: collect(stderr),
+    })
+}
+
+/// Starts `command` as the leader of a new process group, so that it can be
+/// killed together with its children by [`kill_tree`].
+///
+/// Standard input is closed; output goes wherever `command` says.
+pub(crate) fn spawn_group(mut command: Command) -> io::Result<Child> {
+    command.stdin(Stdio::null());
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    command.spawn()
+}
+
+/// Polls `child` until it exits or `deadline` passes.
+pub(crate) fn wait_with_deadline(
+    child: &mut Child,
+    deadline: Instant,
+) -> io::Result<Option<ExitStatus>> {
+    wait_until(child, Some(deadline), None)
+}
+
+/// Polls `child` until it exits, `deadline` passes or `cancelled` is set.
+fn wait_until(
+    child: &mut Child,
+    deadline: Option<Instant>,
+    cancelled: Option<&AtomicBool>,
+) -> io::Result<Option<ExitStatus>> {
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if cancelled.is_some_and(|cancelled| cancelled.load(Ordering::SeqCst)) {
+            return Ok(None);
+        }
+        let now = Instant::now();
+        let pause = match deadline {
+            Some(deadline) if now >= deadline => return Ok(None),
+            Some(deadline) => POLL_INTERVAL.min(deadline - now),
+            None => POLL_INTERVAL,
+        };
+        thread::sleep(pause);
+    }
+}
+
+/// Kills the child together with every process in its process group.
+pub(crate) fn kill_tree(child: &mut Child) {
+    kill_group(child.id());
+    // Also covers platforms without process groups; harmless if already dead.
+    let _ = child.kill();
+}
+
+/// Kills every process in the group led by `pid`.
+fn kill_group(pid: u32) {
+    #[cfg(unix)]
+    {
+        // The child is the leader of its own group, so its pid is the pgid.
+        // SAFETY: `kill` has no memory-safety preconditions.
+        unsafe {
+            libc::kill(-(pid as libc::pid_t), libc::SIGKILL);
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = pid;
+}
+
+/// Like [`run`], but waiting on the tokio runtime instead of blocking.
+///
+/// If the returned future is dropped before the child exits, the child's
+/// whole process group is killed.
+#[cfg(feature = "async")]
+pub(crate) async fn run_async(
+    mut command: Command,
+    timeout: Option<Duration>,
+) -> io::Result<Captured> {
+    use tokio::io::AsyncReadExt;
+
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    let mut child = tokio::process::Command::from(command)
+        .kill_on_drop(true)
+        .spawn()?;
+    let mut group = KillGroupOnDrop(child.id());
+
+    async fn read_all<R: tokio::io::AsyncRead + Unpin>(pipe: Option<R>) -> Vec<u8> {
+        let mut buf = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buf).await;
+        }
+        buf
+    }
+    let stdout = read_all(child.stdout.take());
+    let stderr = read_all(child.stderr.take());
+    let wait = async {
+        let status = match timeout {
+            None => Some(child.wait().await?),
+            Some(timeout) => match tokio::time::timeout(timeout, child.wait()).await {
+                Ok(status) => Some(status?),
+                Err(_) => None,
+            },
+        };
+        if status.is_none() {
+            if let Some(pid) = group.0 {
+                kill_group(pid);
+            }
+            let _ = child.start_kill();
+            child.wait().await?;
+        }
+        io::Result::Ok(status)
+    };
+    let (status, stdout, stderr) = tokio::join!(wait, stdout, stderr);
+    let status = status?;
+    // The child has exited; nothing is left to kill on drop.
+    group.0 = None;
+
+    Ok(Captured {
+        status,
+        stdout: String::from_utf8_lossy(&stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&stderr).into_owned(),
+    })
+}
+
+/// Kills the process group led by the contained pid when dropped.
+#[cfg(feature = "async")]
+struct KillGroupOnDrop(Option<u32>);
+
+#[cfg(feature = "async")]
+impl Drop for KillGroupOnDrop {
+    fn drop(&mut self) {
+        if let Some(pid) = self.0 {
+            kill_group(pid);
+        }
+    }
+}
+
+/// Reads a pipe to the end on a background thread so the child never
+/// blocks on a full pipe buffer.
+fn drain<R: Read + Send + 'static>(pipe: Option<R>) -> Option<JoinHandle<Vec<u8>>> {
+    pipe.map(|mut pipe| {
+        thread::spawn(move || {
+            let mut buf = Vec::new();
+            let _ = pipe.read_to_end(&mut buf);
+            buf
+        })
+    })
+}
+
+fn collect(handle: Option<JoinHandle<Vec<u8>>>) -> String {
+    let bytes = handle
+        .and_then(|handle| handle.join().ok())
+        .unwrap_or_default();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_run_captures_output() {
+        let mut command = Command::new("sh");
+        command.args(["-c", "echo out; echo err >&2; exit 3"]);
+        let captured = run(command, Some(Duration::from_secs(10))).unwrap();
+        assert_eq!(captured.status.unwrap().code(), Some(3));
+        assert_eq!(captured.stdout, "out\n");
+        assert_eq!(captured.stderr, "err\n");
+    }
+
+    #[test]
+    fn test_run_kills_hung_process_tree() {
+        // A fake soffice that hangs, with a background child holding the
+        // output pipes open the way soffice.bin would.
+        let temp_dir = tempdir().unwrap();
+        let script = temp_dir.path().join("soffice");
+        std::fs::write(&script, "#!/bin/sh\necho starting\nsleep 30 &\nsleep 30\n").unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let started = Instant::now();
+        let captured = run(Command::new(&script), Some(Duration::from_millis(300))).unwrap();
+        assert!(captured.status.is_none());
+        assert_eq!(captured.stdout, "starting\n");
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+}
@@ -0,0 +1,139 @@
+//! LibreOffice user profiles (the `UserInstallation` directory).
+//!
+//! LibreOffice locks its user profile while running, so two `soffice`
+//! processes sharing the default profile either fail or silently hand the
+//! work to the instance that is already running. Giving every conversion
+//! its own profile directory lets them run side by side.
+
+use std::ffi::OsString;
+use std::io;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// Which user profile LibreOffice should run with.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ProfileMode {
+    /// Create a fresh temporary profile for each conversion and delete it
+    /// afterwards. Safe to use from many threads at once.
+    #[default]
+    Isolated,
+    /// Use the given directory as the profile. It is created on first use and
+    /// kept, which avoids LibreOffice's profile initialisation on every run.
+    /// Only one conversion may use a given directory at a time.
+    Directory(PathBuf),
+    /// Use LibreOffice's default profile of the current user, like running
+    /// `soffice` by hand. Conversions cannot run concurrently.
+    System,
+}
+
+/// A profile directory in use by one conversion.
+///
+/// Temporary profiles are removed when this is dropped.
+#[derive(Debug)]
+pub(crate) enum Profile {
+    Temporary(TempDir),
+    Persistent(PathBuf),
+    System,
+}
+
+impl Profile {
+    /// Prepares the profile described by `mode`.
+    pub fn prepare(mode: &ProfileMode) -> io::Result<Self> {
+        match mode {
+            ProfileMode::Isolated => tempfile::Builder::new()
+                .prefix("ditto-profile-")
+                .tempdir()
+                .map(Profile::Temporary),
+            ProfileMode::Directory(dir) => {
+                std::fs::create_dir_all(dir)?;
+                Ok(Profile::Persistent(std::path::absolute(dir)?))
+            }
+            ProfileMode::System => Ok(Profile::System),
+        }
+    }
+
+    /// The `-env:UserInstallation=...` argument selecting this profile, if any.
+    pub fn argument(&self) -> Option<OsString> {
+        let dir = match self {
+            Profile::Temporary(dir) => dir.path(),
+            Profile::Persistent(dir) => dir.as_path(),
+            Profile::System => return None,
+        };
+        let mut arg = OsString::from("-env:UserInstallation=");
+        arg.push(file_url(dir));
+        Some(arg)
+    }
+}
+
+/// Formats an absolute path as a `file://` URL, percent-encoding every byte
+/// that is not allowed verbatim in a URL path.
+pub(crate) fn file_url(path: &Path) -> String {
+    let mut encoded = String::new();
+    for &byte in path_bytes(path).iter() {
+        let byte = if cfg!(windows) && byte == b'\\' {
+            b'/'
+        } else {
+            byte
+        };
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            b':' if cfg!(windows) => encoded.push(':'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    if encoded.starts_with('/') {
+        format!("file://{}", encoded)
+    } else {
+        format!("file:///{}", encoded)
+    }
+}
+
+#[cfg(unix)]
+fn path_bytes(path: &Path) -> std::borrow::Cow<'_, [u8]> {
+    use std::os::unix::ffi::OsStrExt;
+    std::borrow::Cow::Borrowed(path.as_os_str().as_bytes())
+}
+
+#[cfg(not(unix))]
+fn path_bytes(path: &Path) -> std::borrow::Cow<'_, [u8]> {
+    match path.to_string_lossy() {
+        std::borrow::Cow::Borrowed(s) => std::borrow::Cow::Borrowed(s.as_bytes()),
+        std::borrow::Cow::Owned(s) => std::borrow::Cow::Owned(s.into_bytes()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_file_url_percent_encodes_path() {
+        use std::os::unix::ffi::OsStrExt;
+        let path = Path::new(std::ffi::OsStr::from_bytes(b"/tmp/my profile/caf\xe9"));
+        assert_eq!(file_url(path), "file:///tmp/my%20profile/caf%E9");
+    }
+
+    #[test]
+    fn test_isolated_profile_is_unique_and_removed_on_drop() {
+        let first = Profile::prepare(&ProfileMode::Isolated).unwrap();
+        let second = Profile::prepare(&ProfileMode::Isolated).unwrap();
+        assert_ne!(first.argument(), second.argument());
+
+        let dir = match &first {
+            Profile::Temporary(dir) => dir.path().to_path_buf(),
+            other => panic!("expected a temporary profile, got {:?}", other),
+        };
+        assert!(dir.is_dir());
+        drop(first);
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn test_system_profile_adds_no_argument() {
+        let profile = Profile::prepare(&ProfileMode::System).unwrap();
+        assert_eq!(profile.argument(), None);
+    }
+}
@@ -0,0 +1,400 @@
+//! The `ditto` Python module.
+//!
+//! Available with the `python` cargo feature and built with maturin. Every
+//! [`DittoError`] is raised as a subclass of `ditto.DittoError`, one per
+//! failure class of the command-line exit codes, and the GIL is released
+//! while LibreOffice runs so other Python threads keep going.
+//!
+//! Every [`PdfExportOptions`] setting is available through the `PdfOptions`
+//! class, and `ditto.FORMATS` lists the output formats.
+
+use crate::{
+    ChangePermission, Converter, DittoError, ErrorKind, OutputFormat, PdfA, PdfExportOptions,
+    PdfPermissions, PrintPermission,
+};
+use exceptions::{
+    ConversionFailedError, ConversionTimeoutError, ConverterNotFoundError, InputError,
+    InvalidOptionsError, OutputError,
+};
+use pyo3::prelude::*;
+use pyo3::types::{PyInt, PyTuple};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// The exceptions raised to Python. `exceptions::DittoError` is the base
+/// class, not the Rust [`DittoError`].
+mod exceptions {
+    use pyo3::create_exception;
+    use pyo3::exceptions::PyException;
+
+    create_exception!(ditto, DittoError, PyException, "A conversion failed.");
+    create_exception!(
+        ditto,
+        InputError,
+        DittoError,
+        "The input is missing, is not a supported document, or is password protected."
+    );
+    create_exception!(
+        ditto,
+        OutputError,
+        DittoError,
+        "The output path is invalid, or the output exists and overwriting is disabled."
+    );
+    create_exception!(
+        ditto,
+        InvalidOptionsError,
+        DittoError,
+        "A conversion option holds a value LibreOffice does not accept."
+    );
+    create_exception!(
+        ditto,
+        ConverterNotFoundError,
+        DittoError,
+        "LibreOffice (soffice) could not be started."
+    );
+    create_exception!(
+        ditto,
+        ConversionTimeoutError,
+        DittoError,
+        "The conversion did not finish within the timeout."
+    );
+    create_exception!(
+        ditto,
+        ConversionFailedError,
+        DittoError,
+        "LibreOffice failed to convert the document."
+    );
+}
+
+impl From<DittoError> for PyErr {
+    fn from(error: DittoError) -> Self {
+        let message = error.to_string();
+        match error.kind() {
+            ErrorKind::Input => InputError::new_err(message),
+            ErrorKind::Output => OutputError::new_err(message),
+            ErrorKind::Usage => InvalidOptionsError::new_err(message),
+            ErrorKind::Unavailable => ConverterNotFoundError::new_err(message),
+            ErrorKind::Timeout => ConversionTimeoutError::new_err(message),
+            ErrorKind::Failed => ConversionFailedError::new_err(message),
+        }
+    }
+}
+
+/// Settings for exporting to PDF, passed to the conversion functions as
+/// `pdf_options`. Anything not given keeps LibreOffice's default.
+///
+/// `printing` is one of `"none"`, `"low"` or `"high"`; `changes` one of
+/// `"none"`, `"pages"`, `"forms"`, `"comments"` or `"any"`. Restricting
+/// permissions requires an `owner_password`.
+#[pyclass(name = "PdfOptions", frozen)]
+#[derive(Clone)]
+struct PyPdfOptions {
+    options: PdfExportOptions,
+}
+
+#[pymethods]
+impl PyPdfOptions {
+    #[new]
+    #[pyo3(signature = (
+        *,
+        pdfa = None,
+        pdf_ua = None,
+        pages = None,
+        jpeg_quality = None,
+        lossless_images = None,
+        reduce_image_resolution = None,
+        export_bookmarks = None,
+        export_notes = None,
+        tagged = None,
+        open_password = None,
+        owner_password = None,
+        printing = None,
+        changes = None,
+        copying = None,
+        accessibility = None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        pdfa: Option<&str>,
+        pdf_ua: Option<bool>,
+        pages: Option<String>,
+        jpeg_quality: Option<&Bound<'_, PyInt>>,
+        lossless_images: Option<bool>,
+        reduce_image_resolution: Option<&Bound<'_, PyInt>>,
+        export_bookmarks: Option<bool>,
+        export_notes: Option<bool>,
+        tagged: Option<bool>,
+        open_password: Option<String>,
+        owner_password: Option<String>,
+        printing: Option<&str>,
+        changes: Option<&str>,
+        copying: Option<bool>,
+        accessibility: Option<bool>,
+    ) -> Result<Self, DittoError> {
+        let mut options = PdfExportOptions::new();
+        if let Some(value) = pdfa {
+            options = options.pdfa(pdfa_level(value)?);
+        }
+        if let Some(enabled) = pdf_ua {
+            options = options.pdf_ua(enabled);
+        }
+        if let Some(range) = pages {
+            options = options.page_range(range);
+        }
+        if let Some(quality) = jpeg_quality {
+            options = options.jpeg_quality(narrow("jpeg_quality", quality, "1 to 100")?);
+        }
+        if let Some(enabled) = lossless_images {
+            options = options.lossless_images(enabled);
+        }
+        if let Some(dpi) = reduce_image_resolution {
+            options = options.reduce_image_resolution(narrow(
+                "reduce_image_resolution",
+                dpi,
+                "75, 150, 300, 600 or 1200",
+            )?);
+        }
+        if let Some(enabled) = export_bookmarks {
+            options = options.export_bookmarks(enabled);
+        }
+        if let Some(enabled) = export_notes {
+            options = options.export_notes(enabled);
+        }
+        if let Some(enabled) = tagged {
+            options = options.tagged(enabled);
+        }
+        if let Some(password) = open_password {
+            options = options.open_password(password);
+        }
+        if let Some(password) = owner_password {
+            options = options.owner_password(password);
+        }
+        if printing.is_some() || changes.is_some() || copying.is_some() || accessibility.is_some() {
+            let mut permissions = PdfPermissions::new();
+            if let Some(value) = printing {
+                permissions = permissions.printing(match value {
+                    "none" => PrintPermission::None,
+                    "low" => PrintPermission::LowResolution,
+                    "high" => PrintPermission::HighResolution,
+                    _ => return Err(invalid_choice("printing", value, "none, low or high")),
+                });
+            }
+            if let Some(value) = changes {
+                permissions = permissions.changes(match value {
+                    "none" => ChangePermission::None,
+                    "pages" => ChangePermission::InsertDeleteRotatePages,
+                    "forms" => ChangePermission::FillForms,
+                    "comments" => ChangePermission::CommentAndFillForms,
+                    "any" => ChangePermission::Any,
+                    _ => {
+                        return Err(invalid_choice(
+                            "changes",
+                            value,
+                            "none, pages, forms, comments or any",
+                        ))
+                    }
+                });
+            }
+            if let Some(allowed) = copying {
+                permissions = permissions.copying(allowed);
+            }
+            if let Some(allowed) = accessibility {
+                permissions = permissions.accessibility(allowed);
+            }
+            options = options.permissions(permissions);
+        }
+        options.validate()?;
+        Ok(PyPdfOptions { options })
+    }
+}
+
+fn pdfa_level(value: &str) -> Result<PdfA, DittoError> {
+    PdfA::parse(value).ok_or_else(|| {
+        DittoError::InvalidOptions(format!(
+            "invalid PDF/A level '{}': expected 1, 2 or 3",
+            value
+        ))
+    })
+}
+
+fn invalid_choice(name: &str, value: &str, expected: &str) -> DittoError {
+    DittoError::InvalidOptions(format!(
+        "invalid {} '{}': expected {}",
+        name, value, expected
+    ))
+}
+
+/// Converts an integer argument to the type of its option. Python integers
+/// are unbounded, and values that do not fit raise `InvalidOptionsError`
+/// like any other invalid value rather than PyO3's `OverflowError`.
+fn narrow<'py, T: FromPyObject<'py>>(
+    name: &str,
+    value: &Bound<'py, PyInt>,
+    expected: &str,
+) -> Result<T, DittoError> {
+    value
+        .extract()
+        .map_err(|_| invalid_choice(name, &value.to_string(), expected))
+}
+
+/// Builds a [`Converter`] from the keyword arguments shared by the module's
+/// functions. `pdfa` and `pages` are shortcuts that override `pdf_options`.
+#[allow(clippy::too_many_arguments)]
+fn converter(
+    timeout: Option<f64>,
+    soffice: Option<PathBuf>,
+    pdf_options: Option<PyPdfOptions>,
+    pdfa: Option<&str>,
+    pages: Option<String>,
+    password: Option<String>,
+    overwrite: bool,
+) -> Result<Converter, DittoError> {
+    let mut pdf_options = pdf_options.map_or_else(PdfExportOptions::new, |pdf| pdf.options);
+    if let Some(value) = pdfa {
+        pdf_options = pdf_options.pdfa(pdfa_level(value)?);
+    }
+    if let Some(range) = pages {
+        pdf_options = pdf_options.page_range(range);
+    }
+
+    let mut converter = Converter::new()
+        .pdf_options(pdf_options)
+        .overwrite(overwrite);
+    if let Some(seconds) = timeout {
+        let timeout = Duration::try_from_secs_f64(seconds)
+            .ok()
+            .filter(|timeout| !timeout.is_zero())
+            .ok_or_else(|| {
+                DittoError::InvalidOptions(format!(
+                    "invalid timeout {}: expected a positive number of seconds",
+                    seconds
+                ))
+            })?;
+        converter = converter.timeout(timeout);
+    }
+    if let Some(soffice) = soffice {
+        converter = converter.program(soffice);
+    }
+    if let Some(password) = password {
+        converter = converter.input_password(password);
+    }
+    Ok(converter)
+}
+
+/// Converts any document LibreOffice can open into `format` (an extension
+/// such as `"pdf"` or `"odt"`, see `FORMATS`) and returns the output path.
+#[pyfunction]
+#[pyo3(signature = (
+    input_path,
+    output_path,
+    format = "pdf",
+    *,
+    timeout = None,
+    soffice = None,
+    pdf_options = None,
+    pdfa = None,
+    pages = None,
+    password = None,
+    overwrite = true,
+))]
+#[allow(clippy::too_many_arguments)]
+fn convert(
+    py: Python<'_>,
+    input_path: PathBuf,
+    output_path: PathBuf,
+    format: &str,
+    timeout: Option<f64>,
+    soffice: Option<PathBuf>,
+    pdf_options: Option<PyPdfOptions>,
+    pdfa: Option<&str>,
+    pages: Option<String>,
+    password: Option<String>,
+    overwrite: bool,
+) -> PyResult<PathBuf> {
+    let format = format
+        .parse::<OutputFormat>()
+        .map_err(|error| InvalidOptionsError::new_err(error.to_string()))?;
+    let converter = converter(
+        timeout,
+        soffice,
+        pdf_options,
+        pdfa,
+        pages,
+        password,
+        overwrite,
+    )?;
+    let report = py.allow_threads(|| converter.convert(&input_path, &output_path, format))?;
+    Ok(report.output_path)
+}
+
+/// Converts a `.docx` file to `.pdf` and returns the output path.
+#[pyfunction]
+#[pyo3(signature = (
+    input_path,
+    output_path,
+    *,
+    timeout = None,
+    soffice = None,
+    pdf_options = None,
+    pdfa = None,
+    pages = None,
+    password = None,
+    overwrite = true,
+))]
+#[allow(clippy::too_many_arguments)]
+fn docx_to_pdf(
+    py: Python<'_>,
+    input_path: PathBuf,
+    output_path: PathBuf,
+    timeout: Option<f64>,
+    soffice: Option<PathBuf>,
+    pdf_options: Option<PyPdfOptions>,
+    pdfa: Option<&str>,
+    pages: Option<String>,
+    password: Option<String>,
+    overwrite: bool,
+) -> PyResult<PathBuf> {
+    convert(
+        py,
+        input_path,
+        output_path,
+        "pdf",
+        timeout,
+        soffice,
+        pdf_options,
+        pdfa,
+        pages,
+        password,
+        overwrite,
+    )
+}
+
+#[pymodule]
+fn ditto(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = module.py();
+    module.add_function(wrap_pyfunction!(docx_to_pdf, module)?)?;
+    module.add_function(wrap_pyfunction!(convert, module)?)?;
+    module.add_class::<PyPdfOptions>()?;
+    module.add(
+        "FORMATS",
+        PyTuple::new(py, OutputFormat::ALL.map(OutputFormat::extension))?,
+    )?;
+    module.add("DittoError", py.get_type::<exceptions::DittoError>())?;
+    module.add("InputError", py.get_type::<InputError>())?;
+    module.add("OutputError", py.get_type::<OutputError>())?;
+    module.add("InvalidOptionsError", py.get_type::<InvalidOptionsError>())?;
+    module.add(
+        "ConverterNotFoundError",
+        py.get_type::<ConverterNotFoundError>(),
+    )?;
+    module.add(
+        "ConversionTimeoutError",
+        py.get_type::<ConversionTimeoutError>(),
+    )?;
+    module.add(
+        "ConversionFailedError",
+        py.get_type::<ConversionFailedError>(),
+    )?;
+    module.add("__version__", env!("CARGO_PKG_VERSION"))?;
+    Ok(())
+}
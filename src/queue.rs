@@ -0,0 +1,539 @@
+//! A job queue that bounds how many conversions run at once.
+//!
+//! Every conversion starts its own LibreOffice, which needs hundreds of
+//! megabytes. Submitting conversions to a [`ConversionQueue`] instead of
+//! calling [`Converter::convert`] from many threads caps the number of
+//! `soffice` processes; the rest wait their turn, by priority.
+
+use crate::{ConversionReport, Converter, DittoError, OutputFormat, Result};
+use std::cmp::Ordering as Order;
+use std::collections::BinaryHeap;
+use std::future::Future;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+use std::thread::JoinHandle;
+
+/// Runs submitted conversions, at most a fixed number at a time.
+///
+/// Created by [`Converter::start_queue`], whose configuration applies to
+/// every job. Waiting jobs run in order of priority, and in the order they
+/// were submitted among equal priorities. The queue can be shared between
+/// threads, for example in an `Arc`.
+///
+/// Dropping the queue cancels the jobs that have not started and waits for
+/// the running ones to finish.
+///
+/// # Example
+/// ```no_run
+/// use ditto::{Converter, OutputFormat};
+///
+/// let queue = Converter::new().start_queue(4);
+/// let urgent = queue.submit_with_priority("urgent.docx", "urgent.pdf", OutputFormat::Pdf, 10);
+/// let report = queue.submit("report.docx", "report.pdf", OutputFormat::Pdf);
+/// println!("{} jobs waiting", queue.depth());
+/// urgent.wait()?;
+/// report.cancel();
+/// # Ok::<(), ditto::DittoError>(())
+/// ```
+#[derive(Debug)]
+pub struct ConversionQueue {
+    shared: Arc<Shared>,
+    runners: Vec<JoinHandle<()>>,
+}
+
+impl ConversionQueue {
+    /// Starts `max_concurrent` runner threads (at least one).
+    pub(crate) fn start(converter: Converter, max_concurrent: usize) -> Self {
+        let shared = Arc::new(Shared {
+            converter,
+            state: Mutex::new(State::default()),
+            work: Condvar::new(),
+        });
+        let runners = (0..max_concurrent.max(1))
+            .map(|_| {
+                let shared = Arc::clone(&shared);
+                std::thread::spawn(move || shared.run())
+            })
+            .collect();
+        ConversionQueue { shared, runners }
+    }
+
+    /// Queues a conversion of `input_path` into `format` at priority 0.
+    ///
+    /// The paths are checked when the job runs; errors are reported through
+    /// the returned handle.
+    pub fn submit(
+        &self,
+        input_path: impl Into<PathBuf>,
+        output_path: impl Into<PathBuf>,
+        format: OutputFormat,
+    ) -> JobHandle {
+        self.submit_with_priority(input_path, output_path, format, 0)
+    }
+
+    /// Queues a conversion that runs before every waiting job of a lower
+    /// `priority`.
+    pub fn submit_with_priority(
+        &self,
+        input_path: impl Into<PathBuf>,
+        output_path: impl Into<PathBuf>,
+        format: OutputFormat,
+        priority: i32,
+    ) -> JobHandle {
+        let job = Arc::new(Job::default());
+        let mut state = self.shared.lock();
+        let sequence = state.submitted;
+        state.submitted += 1;
+        state.waiting.push(Queued {
+            priority,
+            sequence,
+            input_path: input_path.into(),
+            output_path: output_path.into(),
+            format,
+            job: Arc::clone(&job),
+        });
+        drop(state);
+        self.shared.work.notify_one();
+        JobHandle {
+            job,
+            shared: Arc::clone(&self.shared),
+        }
+    }
+
+    /// The number of jobs waiting to run.
+    pub fn depth(&self) -> usize {
+        self.shared.lock().waiting.len()
+    }
+
+    /// The number of jobs running now.
+    pub fn running(&self) -> usize {
+        self.shared.lock().running
+    }
+}
+
+impl Drop for ConversionQueue {
+    fn drop(&mut self) {
+        let waiting = {
+            let mut state = self.shared.lock();
+            state.closed = true;
+            std::mem::take(&mut state.waiting)
+        };
+        self.shared.work.notify_all();
+        for queued in waiting {
+            queued.job.complete(Err(DittoError::Cancelled));
+        }
+        for runner in self.runners.drain(..) {
+            let _ = runner.join();
+        }
+    }
+}
+
+/// A submitted conversion.
+///
+/// Call [`JobHandle::wait`] to block until it has finished, or `.await` the
+/// handle from async code (it does not need a particular runtime).
+/// Dropping the handle does not cancel the job.
+#[derive(Debug)]
+pub struct JobHandle {
+    job: Arc<Job>,
+    shared: Arc<Shared>,
+}
+
+impl JobHandle {
+    /// Blocks until the job has finished and returns its result.
+    pub fn wait(self) -> Result<ConversionReport> {
+        let mut outcome = self.job.lock();
+        loop {
+            if let Some(result) = outcome.result.take() {
+                return result;
+            }
+            outcome = self
+                .job
+                .done
+                .wait(outcome)
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+    }
+
+    /// Whether the job has finished, successfully or not.
+    pub fn is_finished(&self) -> bool {
+        self.job.lock().result.is_some()
+    }
+
+    /// Cancels the job, which then fails with [`DittoError::Cancelled`].
+    ///
+    /// A waiting job is taken off the queue. A running job has LibreOffice
+    /// killed; conversions that go through UNO (see
+    /// [`Converter::python`]) run to completion once started. Does
+    /// nothing if the job has already finished.
+    pub fn cancel(&self) {
+        self.job.cancelled.store(true, Ordering::SeqCst);
+        let mut state = self.shared.lock();
+        let before = state.waiting.len();
+        state
+            .waiting
+            .retain(|queued| !Arc::ptr_eq(&queued.job, &self.job));
+        let removed = state.waiting.len() != before;
+        drop(state);
+        if removed {
+            self.job.complete(Err(DittoError::Cancelled));
+        }
+    }
+}
+
+impl Future for JobHandle {
+    type Output = Result<ConversionReport>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut outcome = self.job.lock();
+        match outcome.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                outcome.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// State shared by the queue, its runners and the handles.
+#[derive(Debug)]
+struct Shared {
+    converter: Converter,
+    state: Mutex<State>,
+    /// Signalled when a job is queued or the queue is closed.
+    work: Condvar,
+}
+
+impl Shared {
+    /// A runner thread: takes the most urgent job until the queue closes.
+    fn run(&self) {
+        loop {
+            let mut state = self.lock();
+            let queued = loop {
+                if state.closed {
+                    return;
+                }
+                match state.waiting.pop() {
+                    Some(queued) => break queued,
+                    None => {
+                        state = self
+                            .work
+                            .wait(state)
+                            .unwrap_or_else(|poisoned| poisoned.into_inner())
+                    }
+                }
+            };
+            state.running += 1;
+            drop(state);
+
+            // A panic (in a `RetryPolicy::retry_if` closure, say) fails the
+            // job instead of taking the runner down with the job unfinished.
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                self.converter.convert_using(
+                    &queued.input_path,
+                    &queued.output_path,
+                    queued.format,
+                    None,
+                    Some(&queued.job.cancelled),
+                )
+            }))
+            .unwrap_or_else(|payload| Err(panicked(payload)));
+            self.lock().running -= 1;
+            queued.job.complete(result);
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// The error a job fails with when its conversion panicked.
+fn panicked(payload: Box<dyn std::any::Any + Send>) -> DittoError {
+    let message = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown cause");
+    DittoError::Io(io::Error::other(format!(
+        "conversion panicked: {}",
+        message
+    )))
+}
+
+#[derive(Debug, Default)]
+struct State {
+    waiting: BinaryHeap<Queued>,
+    running: usize,
+    /// Jobs submitted so far, numbering them in submission order.
+    submitted: u64,
+    closed: bool,
+}
+
+/// A job waiting in the queue.
+#[derive(Debug)]
+struct Queued {
+    priority: i32,
+    sequence: u64,
+    input_path: PathBuf,
+    output_path: PathBuf,
+    format: OutputFormat,
+    job: Arc<Job>,
+}
+
+// The heap pops the greatest element: the highest priority, then the
+// earliest submitted.
+impl Ord for Queued {
+    fn cmp(&self, other: &Self) -> Order {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.sequence.cmp(&self.sequence))
+    }
+}
+
+impl PartialOrd for Queued {
+    fn partial_cmp(&self, other: &Self) -> Option<Order> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Queued {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Order::Equal
+    }
+}
+
+impl Eq for Queued {}
+
+/// The progress of one job, shared with its handle.
+#[derive(Debug, Default)]
+struct Job {
+    cancelled: AtomicBool,
+    outcome: Mutex<Outcome>,
+    /// Signalled when the result is set.
+    done: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct Outcome {
+    /// Set once the job has finished; taken by whoever waits for it.
+    result: Option<Result<ConversionReport>>,
+    /// The task awaiting the handle, if any.
+    waker: Option<Waker>,
+}
+
+impl Job {
+    fn complete(&self, result: Result<ConversionReport>) {
+        let waker = {
+            let mut outcome = self.lock();
+            outcome.result = Some(result);
+            outcome.waker.take()
+        };
+        self.done.notify_all();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Outcome> {
+        self.outcome
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::test_support::{fake_soffice, write_input};
+    use crate::RetryPolicy;
+    use std::path::Path;
+    use std::time::{Duration, Instant};
+    use tempfile::tempdir;
+
+    /// Converts like `CONVERTING_SCRIPT`, logging the start and end of every
+    /// conversion, and holds inputs named `blocker*` until the file `go`
+    /// exists.
+    const LOGGING_SCRIPT: &str = r#"
+here=$(dirname "$0")
+for arg; do
+    case "$prev" in
+        --convert-to) ext="${arg%%:*}" ;;
+        --outdir) outdir="$arg" ;;
+    esac
+    prev="$arg"
+    input="$arg"
+done
+name=$(basename "$input")
+echo "start ${name%.*}" >> "$here/log.txt"
+case "$name" in
+    blocker*) while [ ! -e "$here/go" ]; do sleep 0.02; done ;;
+    slow*) sleep 30 & wait ;;
+    *) sleep 0.1 ;;
+esac
+echo "converted" > "$outdir/${name%.*}.$ext"
+echo "end ${name%.*}" >> "$here/log.txt"
+"#;
+
+    fn log(dir: &Path) -> Vec<String> {
+        std::fs::read_to_string(dir.join("log.txt"))
+            .unwrap_or_default()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    fn wait_for(condition: impl Fn() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !condition() {
+            assert!(Instant::now() < deadline, "timed out waiting");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_queue_limits_concurrency() {
+        let temp_dir = tempdir().unwrap();
+        let soffice = fake_soffice(temp_dir.path(), LOGGING_SCRIPT);
+        let queue = Converter::new().program(&soffice).start_queue(2);
+
+        let handles: Vec<_> = (0..6)
+            .map(|i| {
+                let input = write_input(temp_dir.path(), &format!("doc{}.docx", i));
+                queue.submit(&input, input.with_extension("pdf"), OutputFormat::Pdf)
+            })
+            .collect();
+        for handle in handles {
+            handle.wait().unwrap();
+        }
+
+        let mut running = 0;
+        for line in log(temp_dir.path()) {
+            running += if line.starts_with("start") { 1 } else { -1 };
+            assert!(running <= 2, "more than two conversions at once");
+        }
+        assert_eq!(queue.depth(), 0);
+        assert_eq!(queue.running(), 0);
+    }
+
+    #[test]
+    fn test_queue_runs_by_priority_and_cancels_waiting_jobs() {
+        let temp_dir = tempdir().unwrap();
+        let soffice = fake_soffice(temp_dir.path(), LOGGING_SCRIPT);
+        let queue = Converter::new().program(&soffice).start_queue(1);
+        let submit = |name: &str, priority| {
+            let input = write_input(temp_dir.path(), &format!("{}.docx", name));
+            queue.submit_with_priority(
+                &input,
+                input.with_extension("pdf"),
+                OutputFormat::Pdf,
+                priority,
+            )
+        };
+
+        let blocker = submit("blocker", 0);
+        wait_for(|| queue.running() == 1);
+        let low = submit("low", -1);
+        let first = submit("first", 5);
+        let dropped = submit("dropped", 5);
+        let second = submit("second", 5);
+        assert_eq!(queue.depth(), 4);
+
+        dropped.cancel();
+        assert_eq!(queue.depth(), 3);
+        assert!(dropped.is_finished());
+        assert!(matches!(dropped.wait(), Err(DittoError::Cancelled)));
+
+        std::fs::write(temp_dir.path().join("go"), "").unwrap();
+        for handle in [blocker, first, second, low] {
+            handle.wait().unwrap();
+        }
+        let starts: Vec<_> = log(temp_dir.path())
+            .into_iter()
+            .filter_map(|line| line.strip_prefix("start ").map(str::to_string))
+            .collect();
+        assert_eq!(starts, ["blocker", "first", "second", "low"]);
+        assert!(!temp_dir.path().join("dropped.pdf").exists());
+    }
+
+    #[test]
+    fn test_cancel_kills_running_conversion() {
+        let temp_dir = tempdir().unwrap();
+        let soffice = fake_soffice(temp_dir.path(), LOGGING_SCRIPT);
+        let queue = Converter::new().program(&soffice).start_queue(1);
+        let input = write_input(temp_dir.path(), "slow.docx");
+        let output = temp_dir.path().join("slow.pdf");
+
+        let handle = queue.submit(&input, &output, OutputFormat::Pdf);
+        wait_for(|| log(temp_dir.path()).len() == 1);
+        let started = Instant::now();
+        handle.cancel();
+        assert!(matches!(handle.wait(), Err(DittoError::Cancelled)));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(!output.exists());
+    }
+
+    #[test]
+    fn test_panicking_conversion_fails_its_job() {
+        let temp_dir = tempdir().unwrap();
+        let soffice = fake_soffice(
+            temp_dir.path(),
+            &format!(
+                "case \"$*\" in *broken*) exit 1 ;; esac\n{}",
+                LOGGING_SCRIPT
+            ),
+        );
+        let queue = Converter::new()
+            .program(&soffice)
+            .retry(RetryPolicy::new().retry_if(|_| panic!("retry_if failed")))
+            .start_queue(1);
+
+        let input = write_input(temp_dir.path(), "broken.docx");
+        let handle = queue.submit(&input, input.with_extension("pdf"), OutputFormat::Pdf);
+        match handle.wait() {
+            Err(DittoError::Io(error)) => {
+                assert_eq!(error.to_string(), "conversion panicked: retry_if failed")
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(queue.running(), 0);
+
+        // The runner is still there to take the next job.
+        let input = write_input(temp_dir.path(), "report.docx");
+        queue
+            .submit(&input, input.with_extension("pdf"), OutputFormat::Pdf)
+            .wait()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_handle_can_be_awaited() {
+        let temp_dir = tempdir().unwrap();
+        let soffice = fake_soffice(temp_dir.path(), LOGGING_SCRIPT);
+        let queue = Converter::new().program(&soffice).start_queue(1);
+        let input = write_input(temp_dir.path(), "report.docx");
+        let output = temp_dir.path().join("report.pdf");
+
+        let report = queue
+            .submit(&input, &output, OutputFormat::Pdf)
+            .await
+            .unwrap();
+        assert_eq!(report.output_path, output);
+        assert!(output.is_file());
+
+        let missing = queue.submit(
+            temp_dir.path().join("missing.docx"),
+            &output,
+            OutputFormat::Pdf,
+        );
+        assert!(matches!(missing.await, Err(DittoError::InputNotFound(_))));
+    }
+}
@@ -0,0 +1,50 @@
+//! Information returned by a successful conversion.
+
+use crate::Result;
+use std::path::PathBuf;
+
+/// Details about a conversion that completed successfully.
+///
+/// LibreOffice can print warnings even when it succeeds, so the captured
+/// output is kept around for callers that want to log it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ConversionReport {
+    /// Path of the file that was written.
+    pub output_path: PathBuf,
+    /// Whatever the converter printed to its standard output.
+    pub stdout: String,
+    /// Whatever the converter printed to its standard error.
+    pub stderr: String,
+    /// How many times LibreOffice was run, counting the successful run;
+    /// more than one only with a [`RetryPolicy`](crate::RetryPolicy).
+    pub attempts: u32,
+}
+
+/// The outcome of converting one input of a batch.
+///
+/// Returned by [`Converter::convert_batch`](crate::Converter::convert_batch)
+/// in the order the inputs were given.
+#[derive(Debug)]
+pub struct BatchResult {
+    /// The input this result belongs to.
+    pub input_path: PathBuf,
+    /// The report for the written file, or why this input failed.
+    pub result: Result<ConversionReport>,
+}
+
+/// Details about a successful [`Converter::convert_stream`](crate::Converter::convert_stream).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct StreamReport {
+    /// How many bytes of input were read.
+    pub bytes_read: u64,
+    /// How many bytes of output were written.
+    pub bytes_written: u64,
+    /// Whatever the converter printed to its standard output.
+    pub stdout: String,
+    /// Whatever the converter printed to its standard error.
+    pub stderr: String,
+    /// How many times LibreOffice was run; see [`ConversionReport::attempts`].
+    pub attempts: u32,
+}
@@ -0,0 +1,309 @@
+//! Retrying conversions that failed for reasons that may not recur.
+
+use crate::DittoError;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// When and how often a failed conversion is tried again.
+///
+/// LibreOffice occasionally exits with an error or without writing the
+/// output, typically on the first run after a cold start while it sets up
+/// its profile. With a policy set through [`Converter::retry`], such
+/// conversions are repeated after a delay that doubles on every attempt.
+/// The number of attempts a successful conversion took is reported in
+/// [`ConversionReport::attempts`](crate::ConversionReport::attempts).
+///
+/// # Example
+/// ```no_run
+/// use std::path::Path;
+/// use std::time::Duration;
+/// use ditto::{Converter, RetryPolicy};
+///
+/// let converter = Converter::new().retry(
+///     RetryPolicy::new()
+///         .max_attempts(4)
+///         .backoff(Duration::from_secs(1)),
+/// );
+/// let report = converter.docx_to_pdf(Path::new("in.docx"), Path::new("out.pdf"))?;
+/// println!("converted after {} attempt(s)", report.attempts);
+/// # Ok::<(), ditto::DittoError>(())
+/// ```
+///
+/// [`Converter::retry`]: crate::Converter::retry
+#[derive(Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    backoff: Duration,
+    max_backoff: Duration,
+    retry_if: Arc<dyn Fn(&DittoError) -> bool + Send + Sync>,
+}
+
+impl fmt::Debug for RetryPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("max_attempts", &self.max_attempts)
+            .field("backoff", &self.backoff)
+            .field("max_backoff", &self.max_backoff)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RetryPolicy {
+    /// Up to three attempts, half a second apart at first, retrying the
+    /// errors for which [`DittoError::is_transient`] holds.
+    pub fn new() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(10),
+            retry_if: Arc::new(DittoError::is_transient),
+        }
+    }
+
+    /// Gives up after `attempts` attempts in total (at least one).
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    /// Waits `delay` before the second attempt, twice that before the
+    /// third, and so on.
+    pub fn backoff(mut self, delay: Duration) -> Self {
+        self.backoff = delay;
+        self
+    }
+
+    /// Never waits longer than `delay` between attempts.
+    pub fn max_backoff(mut self, delay: Duration) -> Self {
+        self.max_backoff = delay;
+        self
+    }
+
+    /// Retries the errors for which `retryable` returns `true`, instead of
+    /// the transient ones.
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    /// use std::sync::Arc;
+    /// use ditto::{DittoError, RetryPolicy};
+    ///
+    /// // Also retry conversions that timed out, unless shutting down.
+    /// let shutting_down = Arc::new(AtomicBool::new(false));
+    /// let policy = RetryPolicy::new().retry_if({
+    ///     let shutting_down = Arc::clone(&shutting_down);
+    ///     move |error| {
+    ///         !shutting_down.load(Ordering::Relaxed)
+    ///             && (error.is_transient() || matches!(error, DittoError::Timeout { .. }))
+    ///     }
+    /// });
+    /// ```
+    pub fn retry_if(
+        mut self,
+        retryable: impl Fn(&DittoError) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.retry_if = Arc::new(retryable);
+        self
+    }
+
+    /// How long to wait before trying again after attempt number `attempt`
+    /// (counting from 1) failed with `error`, or `None` to give up.
+    pub(crate) fn delay_after(&self, attempt: u32, error: &DittoError) -> Option<Duration> {
+        if attempt >= self.max_attempts || !(self.retry_if)(error) {
+            return None;
+        }
+        let factor = 2u32.saturating_pow(attempt - 1);
+        Some(self.backoff.saturating_mul(factor).min(self.max_backoff))
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::test_support::{fake_soffice, write_input, CONVERTING_SCRIPT};
+    use crate::{Converter, OutputFormat};
+    use tempfile::tempdir;
+
+    /// Fails (like a cold LibreOffice that writes nothing) until it has been
+    /// run as often as the number in the file `failures`, then converts.
+    fn flaky_script() -> String {
+        format!(
+            r#"here=$(dirname "$0")
+runs=$(cat "$here/runs" 2>/dev/null || echo 0)
+echo $((runs + 1)) > "$here/runs"
+if [ "$runs" -lt "$(cat "$here/failures")" ]; then
+    echo "Error: profile not ready" >&2
+    exit 0
+fi
+{}"#,
+            CONVERTING_SCRIPT
+        )
+    }
+
+    /// Fails like LibreOffice setting up a new profile (without converting)
+    /// unless the profile it is given has been used before.
+    const COLD_PROFILE_SCRIPT: &str = r#"for arg in "$@"; do
+    case "$arg" in
+        -env:UserInstallation=file://*) profile="${arg#-env:UserInstallation=file://}" ;;
+    esac
+done
+if [ ! -e "$profile/initialised" ]; then
+    touch "$profile/initialised"
+    exit 81
+fi
+"#;
+
+    fn runs(dir: &std::path::Path) -> u32 {
+        std::fs::read_to_string(dir.join("runs"))
+            .unwrap()
+            .trim()
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_delay_doubles_up_to_max_backoff() {
+        let policy = RetryPolicy::new()
+            .max_attempts(5)
+            .backoff(Duration::from_secs(1))
+            .max_backoff(Duration::from_secs(3));
+        let failed = DittoError::ConverterFailed {
+            code: Some(1),
+            stdout: String::new(),
+            stderr: String::new(),
+        };
+        let delays: Vec<_> = (1..=5)
+            .map(|attempt| policy.delay_after(attempt, &failed))
+            .collect();
+        assert_eq!(
+            delays,
+            [
+                Some(Duration::from_secs(1)),
+                Some(Duration::from_secs(2)),
+                Some(Duration::from_secs(3)),
+                Some(Duration::from_secs(3)),
+                None,
+            ]
+        );
+        assert_eq!(
+            policy.delay_after(1, &DittoError::InputNotFound("a".into())),
+            None
+        );
+    }
+
+    #[test]
+    fn test_transient_failure_is_retried() {
+        let temp_dir = tempdir().unwrap();
+        let soffice = fake_soffice(temp_dir.path(), &flaky_script());
+        std::fs::write(temp_dir.path().join("failures"), "1").unwrap();
+        let input = write_input(temp_dir.path(), "report.docx");
+        let output = temp_dir.path().join("report.pdf");
+        let policy = RetryPolicy::new().backoff(Duration::from_millis(10));
+
+        let report = Converter::new()
+            .program(&soffice)
+            .retry(policy.clone())
+            .docx_to_pdf(&input, &output)
+            .unwrap();
+        assert_eq!(report.attempts, 2);
+        assert!(output.is_file());
+
+        // Out of attempts: the last error is returned.
+        std::fs::remove_file(temp_dir.path().join("runs")).unwrap();
+        std::fs::write(temp_dir.path().join("failures"), "5").unwrap();
+        let result = Converter::new()
+            .program(&soffice)
+            .retry(policy.max_attempts(3))
+            .convert(&input, &output, OutputFormat::Odt);
+        assert!(matches!(result, Err(DittoError::OutputMissing { .. })));
+        assert_eq!(runs(temp_dir.path()), 3);
+    }
+
+    #[test]
+    fn test_retries_reuse_the_profile() {
+        let temp_dir = tempdir().unwrap();
+        let soffice = fake_soffice(
+            temp_dir.path(),
+            &format!("{}{}", COLD_PROFILE_SCRIPT, CONVERTING_SCRIPT),
+        );
+        let input = write_input(temp_dir.path(), "report.docx");
+        let output = temp_dir.path().join("report.pdf");
+
+        let report = Converter::new()
+            .program(&soffice)
+            .retry(RetryPolicy::new().backoff(Duration::from_millis(10)))
+            .docx_to_pdf(&input, &output)
+            .unwrap();
+        assert_eq!(report.attempts, 2);
+        assert!(output.is_file());
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn test_async_retries_reuse_the_profile() {
+        let temp_dir = tempdir().unwrap();
+        let soffice = fake_soffice(
+            temp_dir.path(),
+            &format!("{}{}", COLD_PROFILE_SCRIPT, CONVERTING_SCRIPT),
+        );
+        let input = write_input(temp_dir.path(), "report.docx");
+
+        let report = Converter::new()
+            .program(&soffice)
+            .retry(RetryPolicy::new().backoff(Duration::from_millis(10)))
+            .docx_to_pdf_async(&input, &temp_dir.path().join("report.pdf"))
+            .await
+            .unwrap();
+        assert_eq!(report.attempts, 2);
+    }
+
+    #[test]
+    fn test_retry_if_can_capture_state() {
+        let temp_dir = tempdir().unwrap();
+        let soffice = fake_soffice(temp_dir.path(), &flaky_script());
+        std::fs::write(temp_dir.path().join("failures"), "5").unwrap();
+        let input = write_input(temp_dir.path(), "report.docx");
+        let seen = Arc::new(std::sync::atomic::AtomicU32::new(0));
+
+        let result = Converter::new()
+            .program(&soffice)
+            .retry(
+                RetryPolicy::new()
+                    .backoff(Duration::from_millis(10))
+                    .retry_if({
+                        let seen = Arc::clone(&seen);
+                        move |_| seen.fetch_add(1, std::sync::atomic::Ordering::SeqCst) < 1
+                    }),
+            )
+            .docx_to_pdf(&input, &temp_dir.path().join("report.pdf"));
+        assert!(matches!(result, Err(DittoError::OutputMissing { .. })));
+        assert_eq!(runs(temp_dir.path()), 2);
+    }
+
+    #[test]
+    fn test_other_errors_are_not_retried() {
+        let temp_dir = tempdir().unwrap();
+        let soffice = fake_soffice(temp_dir.path(), &flaky_script());
+        std::fs::write(temp_dir.path().join("failures"), "0").unwrap();
+        let input = write_input(temp_dir.path(), "report.docx");
+        let converter = Converter::new()
+            .program(&soffice)
+            .retry(RetryPolicy::new().backoff(Duration::from_millis(10)));
+
+        let report = converter
+            .docx_to_pdf(&input, &temp_dir.path().join("report.pdf"))
+            .unwrap();
+        assert_eq!(report.attempts, 1);
+
+        std::fs::write(&input, "not a document").unwrap();
+        let result = converter.docx_to_pdf(&input, &temp_dir.path().join("other.pdf"));
+        assert!(matches!(result, Err(DittoError::UnrecognizedInput { .. })));
+        assert_eq!(runs(temp_dir.path()), 1);
+    }
+}
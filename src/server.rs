@@ -0,0 +1,843 @@
+//! An HTTP service converting uploaded documents, as run by `ditto serve`.
+//!
+//! Available with the `server` cargo feature. Endpoints:
+//!
+//! - `POST /convert` takes a `multipart/form-data` upload with the document
+//!   as a file part and optional `format` (default `pdf`), `pdfa` and
+//!   `pages` fields, and responds with the converted document.
+//! - `GET /health` succeeds if the `soffice` executable can be found.
+//! - `GET /ready` succeeds if LibreOffice actually starts (it runs
+//!   `soffice --version`, one probe at a time, and answers from the last
+//!   result for a few seconds after that).
+//!
+//! Uploads are read as they arrive: the uploaded file is spooled to a
+//! temporary file rather than held in memory, and converted with
+//! [`Converter::convert_stream`], so the converter's checks, timeout and
+//! size limit apply to it. Failures are
+//! answered with a status code matching the error and its message as plain
+//! text.
+
+use crate::locate;
+use crate::process::{self, Captured};
+use crate::profile::Profile;
+use crate::{Converter, DittoError, ErrorKind, OutputFormat, PdfA, Result};
+use std::fs::File;
+use std::io::{self, Read, Seek, Write};
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::path::Path;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tiny_http::{Header, Method, Request, Response, StatusCode};
+
+/// A response with a plain-text body.
+type TextResponse = Response<io::Cursor<Vec<u8>>>;
+
+/// The largest request body accepted unless configured otherwise.
+const DEFAULT_MAX_UPLOAD_SIZE: u64 = 100 * 1024 * 1024;
+
+/// How much of a request body is read at a time.
+const READ_CHUNK: usize = 64 * 1024;
+
+/// The largest part header block or form field accepted. Only uploaded files
+/// may be bigger.
+const MAX_FIELD_SIZE: usize = 64 * 1024;
+
+/// How long `GET /ready` waits for `soffice --version`.
+const READY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long the result of `soffice --version` answers `GET /ready`, so that
+/// frequent readiness probes do not keep starting LibreOffice.
+const READY_TTL: Duration = Duration::from_secs(5);
+
+/// A conversion service listening on a TCP port.
+///
+/// # Example
+/// ```no_run
+/// use std::time::Duration;
+/// use ditto::server::Server;
+/// use ditto::Converter;
+///
+/// let converter = Converter::new().timeout(Duration::from_secs(120));
+/// Server::bind("0.0.0.0:3000", converter)?.threads(4).run();
+/// # Ok::<(), ditto::DittoError>(())
+/// ```
+pub struct Server {
+    http: tiny_http::Server,
+    converter: Converter,
+    threads: usize,
+    max_upload_size: u64,
+    stopping: AtomicBool,
+    /// The last readiness probe; locked while a probe runs.
+    readiness: Mutex<Option<Readiness>>,
+}
+
+/// The outcome of a `GET /ready` probe.
+struct Readiness {
+    checked: Instant,
+    status: u16,
+    message: String,
+}
+
+impl Server {
+    /// Listens on `address`. Requests are not handled until
+    /// [`Server::run`] is called.
+    ///
+    /// # Errors
+    /// [`DittoError::Io`] if the address cannot be bound.
+    pub fn bind(address: impl ToSocketAddrs, converter: Converter) -> Result<Self> {
+        let http = tiny_http::Server::http(address).map_err(|error| {
+            DittoError::Io(io::Error::new(io::ErrorKind::AddrNotAvailable, error))
+        })?;
+        Ok(Server {
+            http,
+            converter,
+            threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            max_upload_size: DEFAULT_MAX_UPLOAD_SIZE,
+            stopping: AtomicBool::new(false),
+            readiness: Mutex::new(None),
+        })
+    }
+
+    /// Handles up to `threads` requests at once (at least one). Defaults to
+    /// the number of available CPUs.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Rejects request bodies larger than `bytes` with
+    /// `413 Payload Too Large`. Defaults to 100 MiB.
+    pub fn max_upload_size(mut self, bytes: u64) -> Self {
+        self.max_upload_size = bytes;
+        self
+    }
+
+    /// The address the server listens on.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.http.server_addr().to_ip()
+    }
+
+    /// Handles requests until [`Server::stop`] is called.
+    pub fn run(&self) {
+        std::thread::scope(|scope| {
+            for _ in 0..self.threads {
+                scope.spawn(|| loop {
+                    match self.http.recv() {
+                        Ok(request) => self.handle(request),
+                        Err(_) if self.stopping.load(Ordering::SeqCst) => break,
+                        Err(_) => {}
+                    }
+                });
+            }
+        });
+    }
+
+    /// Makes [`Server::run`] return once the requests being handled are
+    /// answered.
+    pub fn stop(&self) {
+        self.stopping.store(true, Ordering::SeqCst);
+        for _ in 0..self.threads {
+            self.http.unblock();
+        }
+    }
+
+    fn handle(&self, mut request: Request) {
+        let path = request.url().split('?').next().unwrap_or_default();
+        // The client may have gone away; there is nobody left to tell.
+        let _ = match (request.method(), path) {
+            (Method::Post, "/convert") => match self.convert(&mut request) {
+                Ok(response) => request.respond(response),
+                Err(response) => request.respond(response),
+            },
+            (Method::Get, "/health") => request.respond(self.health()),
+            (Method::Get, "/ready") => request.respond(self.ready()),
+            (_, "/convert" | "/health" | "/ready") => {
+                request.respond(text(405, "method not allowed".to_string()))
+            }
+            _ => request.respond(text(404, "not found".to_string())),
+        };
+    }
+
+    fn convert(&self, request: &mut Request) -> std::result::Result<Response<File>, TextResponse> {
+        let boundary = request
+            .headers()
+            .iter()
+            .find(|header| header.field.equiv("Content-Type"))
+            .and_then(|header| boundary(header.value.as_str()))
+            .ok_or_else(|| text(400, "expected a multipart/form-data upload".to_string()))?;
+        if request
+            .body_length()
+            .is_some_and(|length| length as u64 > self.max_upload_size)
+        {
+            return Err(too_large(self.max_upload_size));
+        }
+        let mut upload = tempfile::tempfile().map_err(|error| error_response(&error.into()))?;
+        // One byte past the limit tells "exactly at" from "over".
+        let mut body = request
+            .as_reader()
+            .take(self.max_upload_size.saturating_add(1));
+        let form = read_form(&mut body, &boundary, &mut upload);
+        if body.limit() == 0 {
+            return Err(too_large(self.max_upload_size));
+        }
+        let form = form.map_err(|error| match error.kind() {
+            io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof => {
+                text(400, "malformed multipart/form-data body".to_string())
+            }
+            _ => text(400, format!("could not read the upload: {}", error)),
+        })?;
+        let filename = form
+            .filename
+            .as_deref()
+            .ok_or_else(|| text(400, "no file was uploaded".to_string()))?;
+        let extension = Path::new(filename)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .ok_or_else(|| text(400, format!("'{}' has no file extension", filename)))?;
+        let field = |name: &str| form.field(name);
+
+        let format = match field("format") {
+            Some(value) => value
+                .parse::<OutputFormat>()
+                .map_err(|error| text(400, error.to_string()))?,
+            None => OutputFormat::Pdf,
+        };
+        let mut pdf_options = self.converter.pdf_options.clone();
+        if let Some(value) = field("pdfa") {
+            let level = PdfA::parse(&value).ok_or_else(|| {
+                text(
+                    400,
+                    format!("invalid value '{}' for 'pdfa': expected 1, 2 or 3", value),
+                )
+            })?;
+            pdf_options = pdf_options.pdfa(level);
+        }
+        if let Some(range) = field("pages") {
+            pdf_options = pdf_options.page_range(range);
+        }
+
+        let converter = self.converter.clone().pdf_options(pdf_options);
+        upload
+            .rewind()
+            .map_err(|error| error_response(&error.into()))?;
+        let mut output = tempfile::tempfile().map_err(|error| error_response(&error.into()))?;
+        converter
+            .convert_stream(upload, &mut output, extension, format)
+            .map_err(|error| error_response(&error))?;
+        output
+            .rewind()
+            .map_err(|error| error_response(&error.into()))?;
+
+        let stem = Path::new(filename)
+            .file_stem()
+            .map(|stem| attachment_name(&stem.to_string_lossy()))
+            .unwrap_or_else(|| "converted".to_string());
+        Ok(Response::from_file(output)
+            .with_header(header("Content-Type", format.media_type()))
+            .with_header(header(
+                "Content-Disposition",
+                &format!("attachment; filename=\"{}.{}\"", stem, format.extension()),
+            )))
+    }
+
+    fn health(&self) -> TextResponse {
+        let program = self.converter.program_path();
+        match locate::which(&program).filter(|path| path.is_file()) {
+            Some(path) => text(200, format!("ok: {}", path.display())),
+            None => text(503, format!("LibreOffice not found: {}", program.display())),
+        }
+    }
+
+    fn ready(&self) -> TextResponse {
+        // Concurrent requests wait for the probe that is running rather than
+        // starting LibreOffice themselves.
+        let mut last = self
+            .readiness
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if last
+            .as_ref()
+            .is_none_or(|last| last.checked.elapsed() >= READY_TTL)
+        {
+            let (status, message) = self.probe();
+            *last = Some(Readiness {
+                checked: Instant::now(),
+                status,
+                message,
+            });
+        }
+        let Readiness {
+            status, message, ..
+        } = last.as_ref().expect("probed above");
+        text(*status, message.clone())
+    }
+
+    /// Runs `soffice --version`, returning the status and message answering
+    /// `GET /ready`.
+    fn probe(&self) -> (u16, String) {
+        let program = self.converter.program_path();
+        let profile = match Profile::prepare(&self.converter.profile) {
+            Ok(profile) => profile,
+            Err(error) => return failure(&error.into()),
+        };
+        let mut command = Command::new(&program);
+        command.args(profile.argument());
+        command.args(["--headless", "--version"]);
+        match process::run(command, Some(READY_TIMEOUT)) {
+            Ok(Captured {
+                status: Some(status),
+                stdout,
+                ..
+            }) if status.success() => (200, format!("ok: {}", stdout.trim())),
+            Ok(Captured {
+                status: Some(status),
+                stderr,
+                ..
+            }) => (
+                503,
+                format!("LibreOffice exited with {}: {}", status, stderr.trim()),
+            ),
+            Ok(Captured { status: None, .. }) => (
+                503,
+                format!("LibreOffice did not start within {:?}", READY_TIMEOUT),
+            ),
+            Err(source) => failure(&DittoError::from_spawn(program, source)),
+        }
+    }
+}
+
+/// The HTTP status answering a failed conversion.
+fn status_for(error: &DittoError) -> u16 {
+    match error.kind() {
+        ErrorKind::Input if matches!(error, DittoError::InputTooLarge { .. }) => 413,
+        ErrorKind::Input => 422,
+        ErrorKind::Usage => 400,
+        ErrorKind::Unavailable => 503,
+        ErrorKind::Timeout => 504,
+        // The output is a temporary file of the server's own.
+        ErrorKind::Output | ErrorKind::Failed => 500,
+    }
+}
+
+/// The status and message answering a failed request.
+fn failure(error: &DittoError) -> (u16, String) {
+    (status_for(error), error.to_string())
+}
+
+fn error_response(error: &DittoError) -> TextResponse {
+    let (status, message) = failure(error);
+    text(status, message)
+}
+
+fn too_large(limit: u64) -> TextResponse {
+    text(413, format!("the upload exceeds {} bytes", limit))
+}
+
+fn text(status: u16, message: String) -> TextResponse {
+    Response::from_string(message + "\n")
+        .with_status_code(StatusCode(status))
+        .with_header(header("Content-Type", "text/plain; charset=utf-8"))
+}
+
+fn header(field: &str, value: &str) -> Header {
+    Header::from_bytes(field, value).expect("header names and values are ASCII")
+}
+
+/// A file name safe to put in a quoted `Content-Disposition` parameter.
+fn attachment_name(stem: &str) -> String {
+    stem.chars()
+        .map(|c| match c {
+            ' '..='~' if c != '"' && c != '\\' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+/// The boundary of a `multipart/form-data` content type.
+fn boundary(content_type: &str) -> Option<String> {
+    let media_type = content_type.split(';').next()?;
+    if !media_type
+        .trim()
+        .eq_ignore_ascii_case("multipart/form-data")
+    {
+        return None;
+    }
+    parameters(content_type)
+        .into_iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("boundary"))
+        .map(|(_, value)| value)
+        .filter(|boundary| !boundary.is_empty())
+}
+
+/// The fields of a `multipart/form-data` body, read by [`read_form`].
+#[derive(Debug, Default)]
+struct Form {
+    /// The file name of the uploaded file, if there was one.
+    filename: Option<String>,
+    /// The other fields, by name, in order.
+    fields: Vec<(String, String)>,
+}
+
+impl Form {
+    /// The trimmed value of the field called `name`, unless it is empty.
+    fn field(&self, name: &str) -> Option<String> {
+        self.fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    }
+}
+
+/// Reads a `multipart/form-data` body (RFC 7578), copying the contents of
+/// the first file part into `upload` and collecting the other fields.
+///
+/// Only a window of the body is held in memory at a time. A malformed body
+/// fails with [`io::ErrorKind::InvalidData`] or, if it ends early,
+/// [`io::ErrorKind::UnexpectedEof`].
+fn read_form(body: impl Read, boundary: &str, upload: &mut impl Write) -> io::Result<Form> {
+    let mut body = Multipart {
+        reader: body,
+        buffer: Vec::new(),
+    };
+    let delimiter = format!("--{}", boundary).into_bytes();
+    body.copy_until(&delimiter, &mut io::sink())?;
+    let delimiter = [&b"\r\n"[..], &delimiter].concat();
+    let mut form = Form::default();
+    loop {
+        if body.skip(b"--")? {
+            return Ok(form);
+        }
+        if !body.skip(b"\r\n")? {
+            return Err(malformed());
+        }
+        let mut headers = Capped::default();
+        body.copy_until(b"\r\n\r\n", &mut headers)?;
+        let headers = String::from_utf8(headers.0).map_err(|_| malformed())?;
+        let (name, filename) = disposition(&headers).ok_or_else(malformed)?;
+        match filename {
+            Some(filename) if form.filename.is_none() => {
+                body.copy_until(&delimiter, upload)?;
+                form.filename = Some(filename);
+            }
+            Some(_) => body.copy_until(&delimiter, &mut io::sink())?,
+            None => {
+                let mut value = Capped::default();
+                body.copy_until(&delimiter, &mut value)?;
+                form.fields
+                    .push((name, String::from_utf8_lossy(&value.0).into_owned()));
+            }
+        }
+    }
+}
+
+/// A request body being read by [`read_form`].
+struct Multipart<R> {
+    reader: R,
+    /// Read but not yet consumed.
+    buffer: Vec<u8>,
+}
+
+impl<R: Read> Multipart<R> {
+    /// Reads more of the body into the buffer; `false` at its end.
+    fn fill(&mut self) -> io::Result<bool> {
+        let mut chunk = [0; READ_CHUNK];
+        let read = loop {
+            match self.reader.read(&mut chunk) {
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                result => break result?,
+            }
+        };
+        self.buffer.extend_from_slice(&chunk[..read]);
+        Ok(read > 0)
+    }
+
+    /// Consumes `prefix` if the body continues with it.
+    fn skip(&mut self, prefix: &[u8]) -> io::Result<bool> {
+        while self.buffer.len() < prefix.len() {
+            if !self.fill()? {
+                return Ok(false);
+            }
+        }
+        let matches = self.buffer.starts_with(prefix);
+        if matches {
+            self.buffer.drain(..prefix.len());
+        }
+        Ok(matches)
+    }
+
+    /// Copies the body up to the next `needle` into `out`, then consumes
+    /// the needle.
+    fn copy_until(&mut self, needle: &[u8], out: &mut impl Write) -> io::Result<()> {
+        loop {
+            if let Some(position) = find(&self.buffer, needle) {
+                out.write_all(&self.buffer[..position])?;
+                self.buffer.drain(..position + needle.len());
+                return Ok(());
+            }
+            // Hold back what may be the start of a needle cut off by the read.
+            let done = self.buffer.len().saturating_sub(needle.len() - 1);
+            out.write_all(&self.buffer[..done])?;
+            self.buffer.drain(..done);
+            if !self.fill()? {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+        }
+    }
+}
+
+/// Collects a part's headers or a form field, up to [`MAX_FIELD_SIZE`].
+#[derive(Default)]
+struct Capped(Vec<u8>);
+
+impl Write for Capped {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if self.0.len() + data.len() > MAX_FIELD_SIZE {
+            return Err(malformed());
+        }
+        self.0.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn malformed() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "malformed multipart/form-data body",
+    )
+}
+
+/// The field name and file name of a part's `Content-Disposition` header.
+fn disposition(headers: &str) -> Option<(String, Option<String>)> {
+    let value = headers.split("\r\n").find_map(|line| {
+        let (field, value) = line.split_once(':')?;
+        field
+            .trim()
+            .eq_ignore_ascii_case("content-disposition")
+            .then_some(value)
+    })?;
+    let mut name = None;
+    let mut filename = None;
+    for (key, value) in parameters(value) {
+        match key.to_ascii_lowercase().as_str() {
+            "name" => name = Some(value),
+            "filename" => filename = Some(value),
+            _ => {}
+        }
+    }
+    Some((name?, filename))
+}
+
+/// The `key=value` parameters following the first `;` of a header value.
+///
+/// Values are tokens or quoted strings, in which `;` has no special meaning
+/// and a backslash escapes the character after it.
+fn parameters(value: &str) -> Vec<(String, String)> {
+    let mut chars = value.chars().peekable();
+    // The value itself, such as `form-data`.
+    up_to_semicolon(&mut chars);
+    let mut params = Vec::new();
+    // Each round starts at a `;`.
+    while chars.next().is_some() {
+        let key: String = std::iter::from_fn(|| chars.next_if(|&c| c != '=' && c != ';')).collect();
+        if chars.next_if_eq(&'=').is_none() {
+            continue;
+        }
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let value = if chars.next_if_eq(&'"').is_some() {
+            let mut value = String::new();
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' => break,
+                    '\\' => value.extend(chars.next()),
+                    c => value.push(c),
+                }
+            }
+            up_to_semicolon(&mut chars);
+            value
+        } else {
+            up_to_semicolon(&mut chars).trim_end().to_string()
+        };
+        params.push((key.trim().to_string(), value));
+    }
+    params
+}
+
+/// Consumes and returns the characters before the next `;`.
+fn up_to_semicolon(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> String {
+    std::iter::from_fn(|| chars.next_if(|&c| c != ';')).collect()
+}
+
+/// The position of the first occurrence of `needle` in `haystack`.
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::test_support::{fake_soffice, write_input, CONVERTING_SCRIPT};
+    use std::io::Write;
+    use std::net::TcpStream;
+    use std::sync::Arc;
+    use tempfile::tempdir;
+
+    const BOUNDARY: &str = "ditto-test-boundary";
+
+    /// Serves `converter` on a free port until the returned guard is dropped.
+    struct Running {
+        server: Arc<Server>,
+        thread: Option<std::thread::JoinHandle<()>>,
+    }
+
+    impl Running {
+        fn start(converter: Converter) -> Self {
+            let server = Arc::new(Server::bind("127.0.0.1:0", converter).unwrap().threads(2));
+            let thread = std::thread::spawn({
+                let server = Arc::clone(&server);
+                move || server.run()
+            });
+            Running {
+                server,
+                thread: Some(thread),
+            }
+        }
+
+        /// Sends a raw request and returns the status code, headers and body.
+        fn request(&self, head: &str, body: &[u8]) -> (u16, String, Vec<u8>) {
+            let mut stream = TcpStream::connect(self.server.local_addr().unwrap()).unwrap();
+            write!(
+                stream,
+                "{}\r\nHost: localhost\r\nConnection: close\r\nContent-Length: {}\r\n\r\n",
+                head,
+                body.len()
+            )
+            .unwrap();
+            stream.write_all(body).unwrap();
+            let mut response = Vec::new();
+            stream.read_to_end(&mut response).unwrap();
+
+            let split = find(&response, b"\r\n\r\n").unwrap();
+            let headers = String::from_utf8(response[..split].to_vec()).unwrap();
+            let status = headers[9..12].parse().unwrap();
+            (status, headers, response[split + 4..].to_vec())
+        }
+
+        fn convert(
+            &self,
+            fields: &[(&str, &str)],
+            file: Option<(&str, &[u8])>,
+        ) -> (u16, String, Vec<u8>) {
+            let mut body = Vec::new();
+            for (name, value) in fields {
+                write!(
+                    body,
+                    "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n",
+                    BOUNDARY, name, value
+                )
+                .unwrap();
+            }
+            if let Some((filename, data)) = file {
+                write!(
+                    body,
+                    "--{}\r\n\
+                     Content-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\n\
+                     Content-Type: application/octet-stream\r\n\r\n",
+                    BOUNDARY, filename
+                )
+                .unwrap();
+                body.extend_from_slice(data);
+                body.extend_from_slice(b"\r\n");
+            }
+            write!(body, "--{}--\r\n", BOUNDARY).unwrap();
+            self.request(
+                &format!(
+                    "POST /convert HTTP/1.1\r\nContent-Type: multipart/form-data; boundary={}",
+                    BOUNDARY
+                ),
+                &body,
+            )
+        }
+    }
+
+    impl Drop for Running {
+        fn drop(&mut self) {
+            self.server.stop();
+            if let Some(thread) = self.thread.take() {
+                thread.join().unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn test_convert_upload() {
+        let temp_dir = tempdir().unwrap();
+        let soffice = fake_soffice(temp_dir.path(), CONVERTING_SCRIPT);
+        let docx = std::fs::read(write_input(temp_dir.path(), "report.docx")).unwrap();
+        let running = Running::start(Converter::new().program(&soffice));
+
+        let (status, headers, body) =
+            running.convert(&[("format", "odt")], Some((r#"Q3 \"final\".docx"#, &docx)));
+        assert_eq!(status, 200, "{}", String::from_utf8_lossy(&body));
+        assert!(headers.contains("application/vnd.oasis.opendocument.text"));
+        assert!(headers.contains("filename=\"Q3 _final_.odt\""));
+        assert_eq!(body, b"%PDF-1.4 converted from input.docx\n");
+
+        let (status, _, _) =
+            running.convert(&[("pdfa", "2"), ("pages", "1-2")], Some(("a.docx", &docx)));
+        assert_eq!(status, 200);
+        let args = std::fs::read_to_string(temp_dir.path().join("args.txt")).unwrap();
+        assert!(args.contains("SelectPdfVersion"));
+    }
+
+    #[test]
+    fn test_convert_errors_map_to_status_codes() {
+        let temp_dir = tempdir().unwrap();
+        let soffice = fake_soffice(temp_dir.path(), CONVERTING_SCRIPT);
+        let docx = std::fs::read(write_input(temp_dir.path(), "report.docx")).unwrap();
+        let running = Running::start(Converter::new().program(&soffice).max_input_size(1024));
+
+        assert_eq!(running.convert(&[], None).0, 400);
+        assert_eq!(
+            running
+                .convert(&[("format", "exe")], Some(("a.docx", &docx)))
+                .0,
+            400
+        );
+        assert_eq!(running.convert(&[], Some(("a.docx", b"plain text"))).0, 422);
+        assert_eq!(running.convert(&[], Some(("a.docx", &[0; 2048]))).0, 413);
+        assert_eq!(
+            running
+                .request("POST /convert HTTP/1.1\r\nContent-Type: text/plain", b"x")
+                .0,
+            400
+        );
+        assert_eq!(running.request("GET /convert HTTP/1.1", b"").0, 405);
+        assert_eq!(running.request("GET /nope HTTP/1.1", b"").0, 404);
+    }
+
+    #[test]
+    fn test_health_and_ready_check_soffice() {
+        let temp_dir = tempdir().unwrap();
+        let soffice = fake_soffice(temp_dir.path(), "echo 'LibreOffice 24.8.4.2'\n");
+        let running = Running::start(Converter::new().program(&soffice));
+        let (status, _, body) = running.request("GET /health HTTP/1.1", b"");
+        assert_eq!(status, 200);
+        assert!(String::from_utf8(body).unwrap().starts_with("ok: "));
+        let (status, _, body) = running.request("GET /ready HTTP/1.1", b"");
+        assert_eq!(status, 200);
+        assert_eq!(body, b"ok: LibreOffice 24.8.4.2\n");
+        drop(running);
+
+        let failing = fake_soffice(temp_dir.path(), "exit 1\n");
+        let running = Running::start(Converter::new().program(&failing));
+        assert_eq!(running.request("GET /ready HTTP/1.1", b"").0, 503);
+        drop(running);
+
+        let running = Running::start(Converter::new().program(temp_dir.path().join("missing")));
+        assert_eq!(running.request("GET /health HTTP/1.1", b"").0, 503);
+        assert_eq!(running.request("GET /ready HTTP/1.1", b"").0, 503);
+    }
+
+    #[test]
+    fn test_ready_probes_are_cached_and_not_concurrent() {
+        let temp_dir = tempdir().unwrap();
+        let soffice = fake_soffice(
+            temp_dir.path(),
+            r#"here=$(dirname "$0")
+echo start >> "$here/runs.txt"
+sleep 0.2
+echo end >> "$here/runs.txt"
+echo 'LibreOffice 24.8.4.2'
+"#,
+        );
+        let running = Running::start(Converter::new().program(&soffice));
+
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| assert_eq!(running.request("GET /ready HTTP/1.1", b"").0, 200));
+            }
+        });
+        assert_eq!(running.request("GET /ready HTTP/1.1", b"").0, 200);
+        let runs = std::fs::read_to_string(temp_dir.path().join("runs.txt")).unwrap();
+        assert_eq!(runs, "start\nend\n");
+    }
+
+    /// Hands out one byte per read, so that every delimiter is cut off.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let Some((first, rest)) = self.0.split_first() else {
+                return Ok(0);
+            };
+            buf[0] = *first;
+            self.0 = rest;
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn test_read_form() {
+        let body = b"preamble\r\n\
+            --b\r\nContent-Disposition: form-data; name=\"format\"\r\n\r\nodt\r\n\
+            --b\r\ncontent-disposition: form-data; name=\"file\"; filename=\"a.docx\"\r\n\r\n\
+            line\r\n-bmore\r\n\
+            --b--\r\n";
+        for whole in [true, false] {
+            let mut upload = Vec::new();
+            let form = if whole {
+                read_form(&body[..], "b", &mut upload)
+            } else {
+                read_form(Trickle(body), "b", &mut upload)
+            }
+            .unwrap();
+            assert_eq!(form.field("format").as_deref(), Some("odt"));
+            assert_eq!(form.filename.as_deref(), Some("a.docx"));
+            assert_eq!(upload, b"line\r\n-bmore");
+        }
+
+        let result = read_form(&b"--b\r\n\r\nno end"[..], "b", &mut io::sink());
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+        let huge_field = [
+            &b"--b\r\nContent-Disposition: form-data; name=\"f\"\r\n\r\n"[..],
+            &vec![b'x'; MAX_FIELD_SIZE + 1],
+            b"\r\n--b--\r\n",
+        ]
+        .concat();
+        let result = read_form(&huge_field[..], "b", &mut io::sink());
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        assert_eq!(
+            boundary("multipart/form-data; boundary=\"x y\"").as_deref(),
+            Some("x y")
+        );
+        assert_eq!(boundary("text/plain; boundary=x"), None);
+    }
+
+    #[test]
+    fn test_disposition_parses_quoted_parameters() {
+        let parse = |value: &str| disposition(&format!("Content-Disposition: {}", value));
+        assert_eq!(
+            parse(r#"form-data; name="file"; filename="a;b.docx""#),
+            Some(("file".to_string(), Some("a;b.docx".to_string())))
+        );
+        assert_eq!(
+            parse(r#"form-data; filename="a\"b.docx"; name=file"#),
+            Some(("file".to_string(), Some("a\"b.docx".to_string())))
+        );
+        assert_eq!(
+            parse(r#"form-data; NAME="c:\\dir""#),
+            Some((r"c:\dir".to_string(), None))
+        );
+        assert_eq!(parse("form-data; filename=a.docx"), None);
+    }
+}
@@ -0,0 +1,440 @@
+//! Identifying input documents from their contents.
+//!
+//! LibreOffice happily starts up for any file and only then reports that it
+//! could not load it (usually by exiting successfully without producing
+//! anything). Looking at the first bytes of the input lets us reject files
+//! that cannot be documents before spawning a process, and tells us what
+//! kind of document we are converting.
+
+use crate::format::OutputFormat;
+use std::fmt;
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// A document format recognised from its bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InputFormat {
+    /// Office Open XML text document.
+    Docx,
+    /// Office Open XML spreadsheet.
+    Xlsx,
+    /// Office Open XML presentation.
+    Pptx,
+    /// OpenDocument text.
+    Odt,
+    /// OpenDocument spreadsheet.
+    Ods,
+    /// OpenDocument presentation.
+    Odp,
+    /// OpenDocument drawing.
+    Odg,
+    /// Legacy Word 97-2003 document (OLE2 compound file).
+    Doc,
+    /// Legacy Excel 97-2003 workbook (OLE2 compound file).
+    Xls,
+    /// Legacy PowerPoint 97-2003 presentation (OLE2 compound file).
+    Ppt,
+    /// Rich Text Format.
+    Rtf,
+    /// Portable Document Format.
+    Pdf,
+}
+
+/// The kind of LibreOffice document an input opens as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DocumentFamily {
+    /// Opened by Writer.
+    Text,
+    /// Opened by Calc.
+    Spreadsheet,
+    /// Opened by Impress.
+    Presentation,
+    /// Opened by Draw (including PDF input).
+    Drawing,
+}
+
+impl InputFormat {
+    /// A short, human-readable name such as `"DOCX"`.
+    pub fn name(self) -> &'static str {
+        match self {
+            InputFormat::Docx => "DOCX",
+            InputFormat::Xlsx => "XLSX",
+            InputFormat::Pptx => "PPTX",
+            InputFormat::Odt => "ODT",
+            InputFormat::Ods => "ODS",
+            InputFormat::Odp => "ODP",
+            InputFormat::Odg => "ODG",
+            InputFormat::Doc => "DOC",
+            InputFormat::Xls => "XLS",
+            InputFormat::Ppt => "PPT",
+            InputFormat::Rtf => "RTF",
+            InputFormat::Pdf => "PDF",
+        }
+    }
+
+    /// The format a file extension claims, for extensions we can verify.
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "docx" | "docm" | "dotx" => Some(InputFormat::Docx),
+            "xlsx" | "xlsm" | "xltx" => Some(InputFormat::Xlsx),
+            "pptx" | "pptm" | "potx" => Some(InputFormat::Pptx),
+            "odt" | "ott" => Some(InputFormat::Odt),
+            "ods" | "ots" => Some(InputFormat::Ods),
+            "odp" | "otp" => Some(InputFormat::Odp),
+            "odg" | "otg" => Some(InputFormat::Odg),
+            "doc" | "dot" => Some(InputFormat::Doc),
+            "xls" | "xlt" => Some(InputFormat::Xls),
+            "ppt" | "pot" => Some(InputFormat::Ppt),
+            "rtf" => Some(InputFormat::Rtf),
+            "pdf" => Some(InputFormat::Pdf),
+            _ => None,
+        }
+    }
+
+    /// Which LibreOffice application opens this format.
+    pub fn family(self) -> DocumentFamily {
+        match self {
+            InputFormat::Docx | InputFormat::Odt | InputFormat::Doc | InputFormat::Rtf => {
+                DocumentFamily::Text
+            }
+            InputFormat::Xlsx | InputFormat::Ods | InputFormat::Xls => DocumentFamily::Spreadsheet,
+            InputFormat::Pptx | InputFormat::Odp | InputFormat::Ppt => DocumentFamily::Presentation,
+            InputFormat::Odg | InputFormat::Pdf => DocumentFamily::Drawing,
+        }
+    }
+}
+
+impl fmt::Display for InputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl DocumentFamily {
+    /// Whether LibreOffice can export documents of this family to `format`.
+    pub fn can_export_to(self, format: OutputFormat) -> bool {
+        use OutputFormat::*;
+        match format {
+            Pdf | Png | Jpg | Svg => true,
+            Html => self != DocumentFamily::Drawing,
+            Docx | Doc | Odt | Rtf | Txt | Epub => self == DocumentFamily::Text,
+            Xlsx | Ods | Csv => self == DocumentFamily::Spreadsheet,
+            Pptx | Odp => self == DocumentFamily::Presentation,
+        }
+    }
+}
+
+/// Identifies the document in `bytes`, or returns `None` if it is not a
+/// format we recognise.
+pub fn detect_bytes(bytes: &[u8]) -> Option<InputFormat> {
+    detect(&mut Cursor::new(bytes)).ok().flatten()
+}
+
+/// Identifies the document stored at `path`.
+///
+/// Only the parts of the file needed for identification are read.
+pub fn detect_file(path: &Path) -> io::Result<Option<InputFormat>> {
+    detect(&mut std::fs::File::open(path)?)
+}
+
+/// Identifies the document read from `reader`.
+pub fn detect<R: Read + Seek>(reader: &mut R) -> io::Result<Option<InputFormat>> {
+    let mut head = Vec::with_capacity(HEAD_LEN);
+    reader.seek(SeekFrom::Start(0))?;
+    reader
+        .by_ref()
+        .take(HEAD_LEN as u64)
+        .read_to_end(&mut head)?;
+
+    if head.starts_with(ZIP_MAGIC) {
+        if let Some(format) = detect_odf(&head) {
+            return Ok(Some(format));
+        }
+        return detect_ooxml(reader);
+    }
+    if head.starts_with(OLE2_MAGIC) {
+        return detect_ole2(reader);
+    }
+    if head.starts_with(b"{\\rtf") {
+        return Ok(Some(InputFormat::Rtf));
+    }
+    // The PDF header may be preceded by junk; readers look in the first KiB.
+    if head[..head.len().min(1024)]
+        .windows(5)
+        .any(|window| window == b"%PDF-")
+    {
+        return Ok(Some(InputFormat::Pdf));
+    }
+    Ok(None)
+}
+
+/// Whether the document read from `reader` is a password-protected Office
+/// Open XML file.
+///
+/// Encrypting a DOCX, XLSX or PPTX turns it into an OLE2 compound file
+/// holding an `EncryptedPackage` stream, so the real format can no longer be
+/// told from the contents. Encrypted legacy and OpenDocument files are not
+/// recognised.
+pub fn is_encrypted<R: Read + Seek>(reader: &mut R) -> io::Result<bool> {
+    let mut magic = [0u8; 8];
+    reader.seek(SeekFrom::Start(0))?;
+    if reader.read_exact(&mut magic).is_err() || magic != OLE2_MAGIC {
+        return Ok(false);
+    }
+    Ok(ole2_stream_names(reader)?
+        .is_some_and(|names| names.iter().any(|name| name == "EncryptedPackage")))
+}
+
+/// How much of the start of a file is read up front.
+const HEAD_LEN: usize = 4096;
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+const OLE2_MAGIC: &[u8] = b"\xD0\xCF\x11\xE0\xA1\xB1\x1A\xE1";
+
+fn u16_at(bytes: &[u8], offset: usize) -> Option<u16> {
+    let bytes = bytes.get(offset..offset + 2)?;
+    Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> Option<u32> {
+    let bytes = bytes.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// OpenDocument files start with an uncompressed `mimetype` entry whose
+/// contents immediately follow its local file header.
+fn detect_odf(head: &[u8]) -> Option<InputFormat> {
+    let name_len = u16_at(head, 26)? as usize;
+    let extra_len = u16_at(head, 28)? as usize;
+    if head.get(30..30 + name_len)? != b"mimetype" {
+        return None;
+    }
+    let start = 30 + name_len + extra_len;
+    let len = u32_at(head, 18)? as usize;
+    let mimetype = head.get(start..start + len)?;
+    let kind = mimetype.strip_prefix(b"application/vnd.oasis.opendocument.")?;
+    if kind.starts_with(b"text") {
+        Some(InputFormat::Odt)
+    } else if kind.starts_with(b"spreadsheet") {
+        Some(InputFormat::Ods)
+    } else if kind.starts_with(b"presentation") {
+        Some(InputFormat::Odp)
+    } else if kind.starts_with(b"graphics") {
+        Some(InputFormat::Odg)
+    } else {
+        None
+    }
+}
+
+/// Office Open XML packages contain `[Content_Types].xml` and keep their
+/// main part under `word/`, `xl/` or `ppt/`.
+fn detect_ooxml<R: Read + Seek>(reader: &mut R) -> io::Result<Option<InputFormat>> {
+    let names = match zip_entry_names(reader)? {
+        Some(names) => names,
+        None => return Ok(None),
+    };
+    if !names.iter().any(|name| name == "[Content_Types].xml") {
+        return Ok(None);
+    }
+    let has_dir = |dir: &str| names.iter().any(|name| name.starts_with(dir));
+    Ok(if has_dir("word/") {
+        Some(InputFormat::Docx)
+    } else if has_dir("xl/") {
+        Some(InputFormat::Xlsx)
+    } else if has_dir("ppt/") {
+        Some(InputFormat::Pptx)
+    } else {
+        None
+    })
+}
+
+/// Lists the entries of a ZIP archive from its central directory.
+fn zip_entry_names<R: Read + Seek>(reader: &mut R) -> io::Result<Option<Vec<String>>> {
+    const EOCD_MAGIC: &[u8] = b"PK\x05\x06";
+    const EOCD_LEN: u64 = 22;
+    // The end-of-central-directory record is followed by a comment of at
+    // most 64 KiB, so it is somewhere in the tail of the file.
+    let len = reader.seek(SeekFrom::End(0))?;
+    let tail_len = len.min(EOCD_LEN + u16::MAX as u64);
+    reader.seek(SeekFrom::Start(len - tail_len))?;
+    let mut tail = Vec::with_capacity(tail_len as usize);
+    reader.read_to_end(&mut tail)?;
+
+    let eocd = match tail.windows(4).rposition(|window| window == EOCD_MAGIC) {
+        Some(position) => &tail[position..],
+        None => return Ok(None),
+    };
+    let (count, cd_size, cd_offset) = match (u16_at(eocd, 10), u32_at(eocd, 12), u32_at(eocd, 16)) {
+        (Some(count), Some(size), Some(offset)) => (count, size as u64, offset as u64),
+        _ => return Ok(None),
+    };
+    if cd_offset + cd_size > len {
+        return Ok(None);
+    }
+
+    let mut directory = vec![0; cd_size as usize];
+    reader.seek(SeekFrom::Start(cd_offset))?;
+    reader.read_exact(&mut directory)?;
+
+    let mut names = Vec::with_capacity(count as usize);
+    let mut offset = 0;
+    for _ in 0..count {
+        if directory.get(offset..offset + 4) != Some(b"PK\x01\x02") {
+            break;
+        }
+        let (name_len, extra_len, comment_len) = match (
+            u16_at(&directory, offset + 28),
+            u16_at(&directory, offset + 30),
+            u16_at(&directory, offset + 32),
+        ) {
+            (Some(name), Some(extra), Some(comment)) => {
+                (name as usize, extra as usize, comment as usize)
+            }
+            _ => break,
+        };
+        match directory.get(offset + 46..offset + 46 + name_len) {
+            Some(name) => names.push(String::from_utf8_lossy(name).into_owned()),
+            None => break,
+        }
+        offset += 46 + name_len + extra_len + comment_len;
+    }
+    Ok(Some(names))
+}
+
+/// Legacy Office files are OLE2 compound files; the application is told
+/// apart by the name of the main stream in the root directory.
+fn detect_ole2<R: Read + Seek>(reader: &mut R) -> io::Result<Option<InputFormat>> {
+    Ok(ole2_stream_names(reader)?.and_then(|names| {
+        names.iter().find_map(|name| match name.as_str() {
+            "WordDocument" => Some(InputFormat::Doc),
+            "Workbook" | "Book" => Some(InputFormat::Xls),
+            "PowerPoint Document" => Some(InputFormat::Ppt),
+            _ => None,
+        })
+    }))
+}
+
+/// Reads the names of the entries in an OLE2 compound file's directory.
+fn ole2_stream_names<R: Read + Seek>(reader: &mut R) -> io::Result<Option<Vec<String>>> {
+    const END_OF_CHAIN: u32 = 0xFFFF_FFFE;
+    // Bounds the walk so a corrupt (cyclic) sector chain cannot hang us.
+    const MAX_DIRECTORY_SECTORS: usize = 256;
+
+    let mut header = [0u8; 512];
+    reader.seek(SeekFrom::Start(0))?;
+    if reader.read_exact(&mut header).is_err() {
+        return Ok(None);
+    }
+    let sector_shift = u16_at(&header, 0x1E).unwrap_or(0);
+    if sector_shift != 9 && sector_shift != 12 {
+        return Ok(None);
+    }
+    let sector_size = 1usize << sector_shift;
+    let difat: Vec<u32> = (0..109)
+        .filter_map(|i| u32_at(&header, 0x4C + i * 4))
+        .collect();
+
+    let read_sector = |reader: &mut R, sector: u32| -> io::Result<Option<Vec<u8>>> {
+        let mut buf = vec![0; sector_size];
+        reader.seek(SeekFrom::Start((sector as u64 + 1) * sector_size as u64))?;
+        Ok(reader.read_exact(&mut buf).ok().map(|_| buf))
+    };
+
+    let mut names = Vec::new();
+    let mut sector = u32_at(&header, 0x30).unwrap_or(END_OF_CHAIN);
+    for _ in 0..MAX_DIRECTORY_SECTORS {
+        if sector >= END_OF_CHAIN - 4 {
+            break;
+        }
+        let directory = match read_sector(reader, sector)? {
+            Some(directory) => directory,
+            None => break,
+        };
+        for entry in directory.chunks_exact(128) {
+            let name_len = (u16_at(entry, 64).unwrap_or(0) as usize).min(64);
+            let units: Vec<u16> = entry[..name_len.saturating_sub(2)]
+                .chunks_exact(2)
+                .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+                .collect();
+            if !units.is_empty() {
+                names.push(String::from_utf16_lossy(&units));
+            }
+        }
+
+        // Follow the chain through the FAT, whose sectors are listed in the
+        // header (files needing more than 109 FAT sectors are not handled).
+        let per_sector = sector_size / 4;
+        let fat_sector = match difat.get(sector as usize / per_sector) {
+            Some(&fat_sector) => fat_sector,
+            None => break,
+        };
+        sector = match read_sector(reader, fat_sector)? {
+            Some(fat) => u32_at(&fat, (sector as usize % per_sector) * 4).unwrap_or(END_OF_CHAIN),
+            None => break,
+        };
+    }
+    Ok(Some(names))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{ole2_with_streams, zip_with_entries};
+
+    #[test]
+    fn test_detects_ooxml_packages() {
+        for (dir, format) in [
+            ("word/document.xml", InputFormat::Docx),
+            ("xl/workbook.xml", InputFormat::Xlsx),
+            ("ppt/presentation.xml", InputFormat::Pptx),
+        ] {
+            let zip = zip_with_entries(&[("[Content_Types].xml", b"<Types/>"), (dir, b"")]);
+            assert_eq!(detect_bytes(&zip), Some(format));
+        }
+        // A ZIP that is not an Office document.
+        let zip = zip_with_entries(&[("word/document.xml", b"")]);
+        assert_eq!(detect_bytes(&zip), None);
+    }
+
+    #[test]
+    fn test_detects_opendocument_from_mimetype() {
+        let zip = zip_with_entries(&[
+            (
+                "mimetype",
+                b"application/vnd.oasis.opendocument.spreadsheet",
+            ),
+            ("content.xml", b""),
+        ]);
+        assert_eq!(detect_bytes(&zip), Some(InputFormat::Ods));
+    }
+
+    #[test]
+    fn test_detects_legacy_office_and_text_formats() {
+        assert_eq!(
+            detect_bytes(&ole2_with_streams(&["WordDocument", "1Table"])),
+            Some(InputFormat::Doc)
+        );
+        assert_eq!(
+            detect_bytes(&ole2_with_streams(&["Workbook"])),
+            Some(InputFormat::Xls)
+        );
+        assert_eq!(detect_bytes(&ole2_with_streams(&["Contents"])), None);
+        let encrypted = ole2_with_streams(&["EncryptionInfo", "EncryptedPackage"]);
+        assert!(is_encrypted(&mut Cursor::new(&encrypted)).unwrap());
+        assert!(!is_encrypted(&mut Cursor::new(ole2_with_streams(&["WordDocument"]))).unwrap());
+        assert_eq!(
+            detect_bytes(b"{\\rtf1\\ansi hello}"),
+            Some(InputFormat::Rtf)
+        );
+        assert_eq!(detect_bytes(b"\n%PDF-1.7\n..."), Some(InputFormat::Pdf));
+        assert_eq!(detect_bytes(b"Test content\n"), None);
+        assert_eq!(detect_bytes(b""), None);
+    }
+
+    #[test]
+    fn test_family_export_compatibility() {
+        assert!(DocumentFamily::Text.can_export_to(OutputFormat::Docx));
+        assert!(DocumentFamily::Spreadsheet.can_export_to(OutputFormat::Pdf));
+        assert!(!DocumentFamily::Spreadsheet.can_export_to(OutputFormat::Docx));
+        assert!(!DocumentFamily::Presentation.can_export_to(OutputFormat::Csv));
+    }
+}
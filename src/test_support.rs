@@ -0,0 +1,161 @@
+//! Helpers shared by the unit tests.
+
+use std::path::{Path, PathBuf};
+
+/// A stand-in for `soffice` that mimics `--convert-to EXT[:FILTER] --outdir
+/// DIR FILE...` by writing `DIR/<stem>.EXT` for every file, and records its
+/// arguments in `args.txt` next to the script. Files named `broken*` are
+/// reported as unloadable and produce nothing, like LibreOffice does.
+pub const CONVERTING_SCRIPT: &str = r#"
+here=$(dirname "$0")
+printf '%s\n' "$@" > "$here/args.txt"
+while [ $# -gt 0 ]; do
+    case "$1" in
+        --convert-to) ext="${2%%:*}"; shift ;;
+        --outdir) outdir="$2"; shift ;;
+        -*) ;;
+        *)
+            name=$(basename "$1")
+            case "$name" in
+                broken*) echo "Error: source file could not be loaded" >&2 ;;
+                *)
+                    printf '%%PDF-1.4 converted from %s\n' "$name" > "$outdir/${name%.*}.$ext"
+                    echo "convert $1 -> $outdir/${name%.*}.$ext"
+                    ;;
+            esac
+            ;;
+    esac
+    shift
+done
+"#;
+
+/// A fake Python for the UNO helper: accepts the password `secret` from the
+/// environment and writes the export filter name to the output URL.
+pub const UNO_HELPER_SCRIPT: &str = r#"
+while [ $# -gt 0 ]; do
+  case "$1" in
+    --output) output="${2#file://}"; shift ;;
+    --filter) filter="$2"; shift ;;
+  esac
+  shift
+done
+if [ "$DITTO_INPUT_PASSWORD" != secret ]; then
+  echo "Error: document is password protected" >&2
+  exit 10
+fi
+echo "$filter" > "$output"
+"#;
+
+/// Writes an executable shell script named `soffice` into `dir`.
+#[cfg(unix)]
+pub fn fake_soffice(dir: &Path, body: &str) -> PathBuf {
+    fake_program(dir, "soffice", body)
+}
+
+/// Writes an executable shell script called `name` into `dir`.
+#[cfg(unix)]
+pub fn fake_program(dir: &Path, name: &str, body: &str) -> PathBuf {
+    use std::os::unix::fs::PermissionsExt;
+
+    let script = dir.join(name);
+    std::fs::write(&script, format!("#!/bin/sh\n{}", body)).unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+    script
+}
+
+/// Writes a minimal document into `dir` whose contents match the extension
+/// of `name` (a DOCX-like package unless the extension says otherwise).
+pub fn write_input(dir: &Path, name: &str) -> PathBuf {
+    let main_part = match Path::new(name).extension().and_then(|e| e.to_str()) {
+        Some("xlsx") => "xl/workbook.xml",
+        Some("pptx") => "ppt/presentation.xml",
+        _ => "word/document.xml",
+    };
+    let path = dir.join(name);
+    std::fs::write(
+        &path,
+        zip_with_entries(&[("[Content_Types].xml", b"<Types/>"), (main_part, b"")]),
+    )
+    .unwrap();
+    path
+}
+
+/// Builds an uncompressed ZIP archive containing `entries`, in order.
+///
+/// CRCs are left at zero; the code under test never checks them.
+pub fn zip_with_entries(entries: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut zip = Vec::new();
+    let mut directory = Vec::new();
+    for (name, data) in entries {
+        let offset = zip.len() as u32;
+        let header = |magic: &[u8], central: bool| {
+            let mut header = magic.to_vec();
+            if central {
+                header.extend_from_slice(&20u16.to_le_bytes());
+            }
+            header.extend_from_slice(&20u16.to_le_bytes());
+            header.extend_from_slice(&[0; 10]); // flags, method, time, date, crc
+            header.extend_from_slice(&[0; 2]);
+            header.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            header.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            header.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            header.extend_from_slice(&0u16.to_le_bytes());
+            header
+        };
+        zip.extend(header(b"PK\x03\x04", false));
+        zip.extend_from_slice(name.as_bytes());
+        zip.extend_from_slice(data);
+
+        directory.extend(header(b"PK\x01\x02", true));
+        directory.extend_from_slice(&[0; 10]); // comment, disk, attributes
+        directory.extend_from_slice(&offset.to_le_bytes());
+        directory.extend_from_slice(name.as_bytes());
+    }
+    let directory_offset = zip.len() as u32;
+    zip.extend_from_slice(&directory);
+    zip.extend_from_slice(b"PK\x05\x06");
+    zip.extend_from_slice(&[0; 4]);
+    zip.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    zip.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    zip.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+    zip.extend_from_slice(&directory_offset.to_le_bytes());
+    zip.extend_from_slice(&0u16.to_le_bytes());
+    zip
+}
+
+/// Builds a minimal OLE2 compound file whose directory lists `streams`
+/// (at most three) after the root entry.
+pub fn ole2_with_streams(streams: &[&str]) -> Vec<u8> {
+    const FREE: u32 = 0xFFFF_FFFF;
+    let mut file = vec![0u8; 512 * 3];
+    file[..8].copy_from_slice(b"\xD0\xCF\x11\xE0\xA1\xB1\x1A\xE1");
+    file[0x1E..0x20].copy_from_slice(&9u16.to_le_bytes());
+    file[0x2C..0x30].copy_from_slice(&1u32.to_le_bytes());
+    // Sector 0 holds the FAT, sector 1 the directory.
+    file[0x30..0x34].copy_from_slice(&1u32.to_le_bytes());
+    for i in 0..109 {
+        let value: u32 = if i == 0 { 0 } else { FREE };
+        file[0x4C + i * 4..0x50 + i * 4].copy_from_slice(&value.to_le_bytes());
+    }
+    let fat = &mut file[512..1024];
+    for (i, chunk) in fat.chunks_exact_mut(4).enumerate() {
+        let value: u32 = match i {
+            0 => 0xFFFF_FFFD,
+            1 => 0xFFFF_FFFE,
+            _ => FREE,
+        };
+        chunk.copy_from_slice(&value.to_le_bytes());
+    }
+    let directory = &mut file[1024..];
+    for (entry, name) in directory
+        .chunks_exact_mut(128)
+        .zip(std::iter::once("Root Entry").chain(streams.iter().copied()))
+    {
+        let units: Vec<u16> = name.encode_utf16().chain(std::iter::once(0)).collect();
+        for (i, unit) in units.iter().enumerate() {
+            entry[i * 2..i * 2 + 2].copy_from_slice(&unit.to_le_bytes());
+        }
+        entry[64..66].copy_from_slice(&((units.len() * 2) as u16).to_le_bytes());
+    }
+    file
+}
@@ -0,0 +1,141 @@
+//! Converting through a LibreOffice UNO listener.
+//!
+//! `soffice --convert-to` has no way to pass options used while *loading* a
+//! document, such as the password of an encrypted input, and takes export
+//! options on its command line, where any local user can read the passwords
+//! of an encrypted PDF. For those conversions LibreOffice is started as a
+//! listener instead and driven by a small Python-UNO script, which runs with
+//! the Python interpreter that can `import uno` (bundled with LibreOffice or
+//! installed as `python3-uno`).
+
+use crate::process::{self, Captured};
+use crate::profile::{file_url, Profile};
+use crate::{DittoError, Result};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// The helper script, passed to Python with `-c`.
+const SCRIPT: &str = include_str!("uno_convert.py");
+
+/// Exit code of the helper when it cannot reach the listener.
+pub(crate) const EXIT_CONNECT: i32 = 2;
+
+/// Exit code of the helper when the document needs a (different) password.
+pub(crate) const EXIT_PASSWORD: i32 = 10;
+
+/// How long the helper keeps retrying to reach a listener that is starting.
+pub(crate) const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How long a listener is given to exit on its own once told to terminate.
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
+
+/// One document to convert over UNO.
+#[derive(Debug)]
+pub(crate) struct Job<'a> {
+    pub input: &'a Path,
+    pub output: &'a Path,
+    pub filter: &'a str,
+    pub filter_options: Option<&'a str>,
+    /// Export filter data in the JSON syntax of `PdfExportOptions`, which
+    /// may hold passwords.
+    pub filter_data: Option<String>,
+    pub password: Option<&'a str>,
+}
+
+/// A connection string naming a pipe no other listener uses.
+pub(crate) fn unique_connection() -> String {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    format!(
+        "pipe,name=ditto-{}-{}",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// The command starting a headless listener on `connection`.
+pub(crate) fn listener_command(program: &Path, profile: &Profile, connection: &str) -> Command {
+    let mut command = Command::new(program);
+    command.args(profile.argument());
+    command.args([
+        "--headless",
+        "--invisible",
+        "--nologo",
+        "--nodefault",
+        "--norestore",
+        "--nolockcheck",
+        &format!("--accept={};urp;StarOffice.ComponentContext", connection),
+    ]);
+    command
+}
+
+/// The command running the helper script for `job` against `connection`.
+///
+/// The input password and the filter data, which holds the passwords of an
+/// encrypted PDF, travel in the environment rather than on the command line.
+pub(crate) fn helper_command(
+    python: &Path,
+    connection: &str,
+    job: &Job<'_>,
+    connect_timeout: Duration,
+    terminate: bool,
+) -> Result<Command> {
+    let absolute = |path: &Path| std::path::absolute(path).map(|path| file_url(&path));
+    let mut command = Command::new(python);
+    command.arg("-c").arg(SCRIPT);
+    command.args([
+        "--connect",
+        connection,
+        "--connect-timeout",
+        &connect_timeout.as_secs_f64().to_string(),
+        "--filter",
+        job.filter,
+    ]);
+    command.arg("--input").arg(absolute(job.input)?);
+    command.arg("--output").arg(absolute(job.output)?);
+    if let Some(options) = job.filter_options {
+        command.args(["--filter-options", options]);
+    }
+    if terminate {
+        command.arg("--terminate");
+    }
+    match job.password {
+        Some(password) => command.env("DITTO_INPUT_PASSWORD", password),
+        None => command.env_remove("DITTO_INPUT_PASSWORD"),
+    };
+    match &job.filter_data {
+        Some(data) => command.env("DITTO_FILTER_DATA", data),
+        None => command.env_remove("DITTO_FILTER_DATA"),
+    };
+    Ok(command)
+}
+
+/// Starts a listener, converts `job` through it and shuts it down again.
+pub(crate) fn convert_once(
+    program: &Path,
+    python: &Path,
+    profile: &Profile,
+    job: &Job<'_>,
+    timeout: Option<Duration>,
+) -> Result<Captured> {
+    let connection = unique_connection();
+    let mut listener = listener_command(program, profile, &connection);
+    listener.stdout(Stdio::null()).stderr(Stdio::null());
+    let mut listener = process::spawn_group(listener)
+        .map_err(|source| DittoError::from_spawn(program.to_path_buf(), source))?;
+
+    let connect_timeout = timeout.unwrap_or(DEFAULT_CONNECT_TIMEOUT);
+    let result =
+        helper_command(python, &connection, job, connect_timeout, true).and_then(|helper| {
+            process::run(helper, timeout)
+                .map_err(|source| DittoError::from_spawn(python.to_path_buf(), source))
+        });
+
+    let exited = process::wait_with_deadline(&mut listener, Instant::now() + SHUTDOWN_GRACE);
+    if !matches!(exited, Ok(Some(_))) {
+        process::kill_tree(&mut listener);
+        let _ = listener.wait();
+    }
+    result
+}
@@ -0,0 +1,155 @@
+# Converts one document through a running LibreOffice listener over UNO.
+#
+# Run by ditto with LibreOffice's Python (the one that can `import uno`).
+# The input password, if any, is read from the DITTO_INPUT_PASSWORD
+# environment variable, and the export filter data (which holds the passwords
+# of an encrypted PDF) from DITTO_FILTER_DATA, so that neither shows up in the
+# process list.
+#
+# Exit codes:
+#   0   converted
+#   1   loading or exporting failed
+#   2   could not connect to the listener
+#   10  the document is password protected and no or a wrong password was given
+
+import argparse
+import json
+import os
+import sys
+import time
+
+import uno
+import unohelper
+from com.sun.star.beans import PropertyValue
+from com.sun.star.connection import NoConnectException
+from com.sun.star.task import XInteractionHandler
+
+EXIT_FAILED = 1
+EXIT_CONNECT = 2
+EXIT_PASSWORD = 10
+
+
+def properties(**values):
+    result = []
+    for name, value in values.items():
+        prop = PropertyValue()
+        prop.Name = name
+        prop.Value = value
+        result.append(prop)
+    return tuple(result)
+
+
+def filter_data(text):
+    """Turns ditto's `{"Name":{"type":..,"value":..}}` JSON into PropertyValues."""
+    result = []
+    for name, spec in json.loads(text).items():
+        kind, value = spec["type"], spec["value"]
+        if kind == "boolean":
+            value = value == "true"
+        elif kind == "long":
+            value = int(value)
+        prop = PropertyValue()
+        prop.Name = name
+        prop.Value = value
+        result.append(prop)
+    return uno.Any("[]com.sun.star.beans.PropertyValue", tuple(result))
+
+
+class Handler(unohelper.Base, XInteractionHandler):
+    """Aborts every interaction, remembering whether a password was asked for."""
+
+    def __init__(self):
+        self.password_requested = False
+
+    def handle(self, request):
+        detail = request.getRequest()
+        if "PasswordRequest" in type(detail).__name__ or (
+            hasattr(detail, "Mode") and hasattr(detail, "Name")
+        ):
+            self.password_requested = True
+        abort = uno.getTypeByName("com.sun.star.task.XInteractionAbort")
+        for continuation in request.getContinuations():
+            if continuation.queryInterface(abort) is not None:
+                continuation.select()
+                return
+
+
+def connect(connection, timeout):
+    local = uno.getComponentContext()
+    resolver = local.ServiceManager.createInstanceWithContext(
+        "com.sun.star.bridge.UnoUrlResolver", local
+    )
+    url = "uno:%s;urp;StarOffice.ComponentContext" % connection
+    deadline = time.monotonic() + timeout
+    while True:
+        try:
+            return resolver.resolve(url)
+        except NoConnectException:
+            if time.monotonic() >= deadline:
+                raise
+            time.sleep(0.25)
+
+
+def main():
+    parser = argparse.ArgumentParser()
+    parser.add_argument("--connect", required=True)
+    parser.add_argument("--connect-timeout", type=float, default=60.0)
+    parser.add_argument("--input", required=True)
+    parser.add_argument("--output", required=True)
+    parser.add_argument("--filter", required=True)
+    parser.add_argument("--filter-options")
+    parser.add_argument("--terminate", action="store_true")
+    args = parser.parse_args()
+
+    try:
+        context = connect(args.connect, args.connect_timeout)
+    except Exception as e:
+        print("Error: could not connect to LibreOffice: %s" % e, file=sys.stderr)
+        return EXIT_CONNECT
+    desktop = context.ServiceManager.createInstanceWithContext(
+        "com.sun.star.frame.Desktop", context
+    )
+
+    handler = Handler()
+    load = dict(Hidden=True, ReadOnly=True, InteractionHandler=handler)
+    password = os.environ.get("DITTO_INPUT_PASSWORD")
+    if password is not None:
+        load["Password"] = password
+
+    status = 0
+    document = None
+    try:
+        document = desktop.loadComponentFromURL(args.input, "_blank", 0, properties(**load))
+        if document is None:
+            raise RuntimeError("source file could not be loaded")
+        store = dict(FilterName=args.filter, Overwrite=True)
+        if args.filter_options:
+            store["FilterOptions"] = args.filter_options
+        data = os.environ.get("DITTO_FILTER_DATA")
+        if data:
+            store["FilterData"] = filter_data(data)
+        document.storeToURL(args.output, properties(**store))
+    except Exception as e:
+        if handler.password_requested:
+            print("Error: document is password protected", file=sys.stderr)
+            status = EXIT_PASSWORD
+        else:
+            print("Error: %s" % e, file=sys.stderr)
+            status = EXIT_FAILED
+    finally:
+        if document is not None:
+            try:
+                document.close(True)
+            except Exception:
+                pass
+        if args.terminate:
+            try:
+                desktop.terminate()
+            except Exception:
+                # The bridge goes away while terminating; that is expected.
+                pass
+    return status
+
+
+if __name__ == "__main__":
+    sys.exit(main())